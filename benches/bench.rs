@@ -0,0 +1,43 @@
+extern crate plause;
+extern crate test;
+
+use std::io::util::NullWriter;
+use test::Bencher;
+use plause::Plause;
+
+static MIB: u32 = 1048576;
+
+/// generate a blocksize blob holding items pieces of item_len bytes, written to a sink
+fn gen(b: &mut Bencher, blocksize: u32, items: uint, item_len: uint) {
+    let content: Vec<u8> = Vec::from_elem(item_len, 0x42u8);
+
+    b.bytes = blocksize as u64;
+    b.iter(|| {
+        let mut plause = Plause::with_seed(blocksize, "saltysaltsalt", 1);
+
+        //password stretching is deliberately slow and would drown out the layout cost
+        plause.settings.kdf = None;
+
+        for _ in range(0, items) {
+            let password = plause.random_pass(24);
+            plause.add(&password, &content).unwrap();
+        }
+
+        plause.gen_to(&mut NullWriter).unwrap();
+    });
+}
+
+#[bench]
+fn gen_16mib_256_items(b: &mut Bencher) {
+    gen(b, 16 * MIB, 256, 4096);
+}
+
+#[bench]
+fn gen_256mib_256_items(b: &mut Bencher) {
+    gen(b, 256 * MIB, 256, 4096);
+}
+
+#[bench]
+fn gen_1gib_512_items(b: &mut Bencher) {
+    gen(b, 1024 * MIB, 512, 4096);
+}
@@ -0,0 +1,109 @@
+use std::u32;
+use random::RandomSource;
+
+/// Allocator tracks the free gaps of a block so pieces never overlap and never exceed it
+#[deriving(Clone,Show)]
+pub struct Allocator {
+    /// sorted, non empty and non touching [start, end) ranges
+    free: Vec<(u32, u32)>,
+}
+
+impl Allocator {
+    pub fn new(blocksize: u32) -> Allocator {
+        Allocator {
+            free: if blocksize > 0 { vec![(0, blocksize)] } else { Vec::new() },
+        }
+    }
+
+    /// take a range of len bytes starting at a position picked uniformly among
+    /// every position it fits, None only if no gap can hold it
+    pub fn alloc<R:RandomSource>(&mut self, rng: &mut R, len: u32) -> Option<u32> {
+        if len == 0 {
+            return None;
+        }
+
+        //every gap of at least len offers gap - len + 1 starts, the sum never exceeds the blocksize
+        let positions = self.free.iter()
+            .filter(|&&(s, e)| e - s >= len)
+            .fold(0u32, |acc, &(s, e)| acc + (e - s - len + 1));
+
+        if positions == 0 {
+            return None;
+        }
+
+        let mut pick = rng.below(positions);
+
+        for &(s, e) in self.free.iter() {
+            if e - s < len {
+                continue;
+            }
+
+            let starts = e - s - len + 1;
+
+            if pick < starts {
+                let start = s + pick;
+                self.reserve(start, len);
+                return Some(start);
+            }
+
+            pick -= starts;
+        }
+
+        unreachable!()
+    }
+
+    /// take exactly start..start + len if all of it is free
+    pub fn reserve(&mut self, start: u32, len: u32) -> bool {
+        if start > u32::MAX - len {
+            return false;
+        }
+
+        let end = start + len;
+
+        //the only gap that can hold it is the last one starting at or before start,
+        //found by bisection as scattered pieces reserve a cell at a time
+        let mut lo = 0u;
+        let mut hi = self.free.len();
+
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+
+            match self.free[mid] {
+                (s, _) if s <= start => lo = mid + 1,
+                _                    => hi = mid,
+            }
+        }
+
+        if lo == 0 {
+            return false;
+        }
+
+        let idx = lo - 1;
+
+        match self.free[idx] {
+            (_, e) if end <= e => { },
+            _                  => return false,
+        }
+
+        let (s, e) = self.free.remove(idx).unwrap();
+
+        if end < e {
+            self.free.insert(idx, (end, e));
+        }
+        if s < start {
+            self.free.insert(idx, (s, start));
+        }
+
+        true
+    }
+
+    /// total number of unallocated bytes
+    pub fn free_space(&self) -> u32 {
+        self.free.iter().fold(0u32, |acc, &(s, e)| acc + (e - s))
+    }
+
+    /// length of the largest range alloc could still hand out
+    pub fn largest_gap(&self) -> u32 {
+        self.free.iter().fold(0u32, |acc, &(s, e)| if e - s > acc { e - s } else { acc })
+    }
+}
@@ -0,0 +1,127 @@
+extern crate flate;
+
+use std::fmt;
+use error::{PlauseResult, IntegrityError};
+
+/// header flag marking deflated content
+pub static FLAG_DEFLATE: u8 = 0x01;
+
+/// content is deflated in blocks of this many bytes each inflated on its own, so extraction
+/// only ever holds one block and what it inflates to
+pub static DEFLATE_BLOCK: uint = 32768;
+
+/// bytes before every deflated block giving its length
+pub static BLOCK_HEADER: uint = 4;
+
+/// the longest a deflated block may be, deflate only grows incompressible input by a few bytes
+/// in every thousand. nothing this long can inflate to more than about 1032 times it
+static MAX_DEFLATED_BLOCK: uint = DEFLATE_BLOCK + DEFLATE_BLOCK / 64 + 64;
+
+/// Compression is applied to an item's content before it is encrypted, it is recorded
+/// in the piece's encrypted header so extraction undoes it without being told
+#[deriving(Clone,PartialEq,Eq)]
+pub enum Compression {
+    NoCompression,
+    CompressDeflate,
+}
+
+impl Compression {
+    pub fn from_name(name: &str) -> Option<Compression> {
+        match name {
+            "none"    => Some(NoCompression),
+            "deflate" => Some(CompressDeflate),
+            _         => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            NoCompression   => "none",
+            CompressDeflate => "deflate",
+        }
+    }
+
+    /// header flags of content compressed this way
+    pub fn flags(&self) -> u8 {
+        match *self {
+            NoCompression   => 0,
+            CompressDeflate => FLAG_DEFLATE,
+        }
+    }
+
+    /// content compressed this way, None if that wouldn't make it any smaller
+    pub fn compress(&self, content: &[u8]) -> Option<Vec<u8>> {
+        let packed = match *self {
+            NoCompression   => return None,
+            CompressDeflate => {
+                let mut packed: Vec<u8> = Vec::new();
+
+                for block in content.chunks(DEFLATE_BLOCK) {
+                    let deflated = match flate::deflate_bytes(block) {
+                        Some(x) => x.as_slice().to_vec(),
+                        None    => return None,
+                    };
+
+                    packed.extend(range(0u, BLOCK_HEADER).map(|i| (deflated.len() >> (24 - 8 * i)) as u8));
+                    packed.push_all(deflated.as_slice());
+                }
+
+                packed
+            },
+        };
+
+        match packed.len() < content.len() {
+            true  => Some(packed),
+            false => None,
+        }
+    }
+}
+
+impl fmt::Show for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// length of the deflated block whose header begins header, None if the header is incomplete
+/// or gives more than a block can deflate to
+pub fn block_len(header: &[u8]) -> Option<uint> {
+    if header.len() < BLOCK_HEADER {
+        return None;
+    }
+
+    match header[..BLOCK_HEADER].iter().fold(0u, |acc, &x| (acc << 8) | x as uint) {
+        x if x <= MAX_DEFLATED_BLOCK => Some(x),
+        _                            => None,
+    }
+}
+
+/// inflate a single deflated block, refusing one that doesn't come back as at most DEFLATE_BLOCK bytes
+pub fn inflate_block(block: &[u8]) -> PlauseResult<Vec<u8>> {
+    match flate::inflate_bytes(block) {
+        Some(x) if x.as_slice().len() <= DEFLATE_BLOCK => Ok(x.as_slice().to_vec()),
+        _                                              => Err(IntegrityError),
+    }
+}
+
+/// undo the compression flags say content went through
+pub fn decompress(flags: u8, content: Vec<u8>) -> PlauseResult<Vec<u8>> {
+    if flags & FLAG_DEFLATE == 0 {
+        return Ok(content);
+    }
+
+    let mut result: Vec<u8> = Vec::new();
+    let mut rest = content.as_slice();
+
+    while !rest.is_empty() {
+        let len = match block_len(rest) {
+            Some(x) if BLOCK_HEADER + x <= rest.len() => x,
+            _                                         => return Err(IntegrityError),
+        };
+
+        result.push_all(try!(inflate_block(rest[BLOCK_HEADER..BLOCK_HEADER + len])).as_slice());
+        rest = rest[BLOCK_HEADER + len..];
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,78 @@
+use std::cmp::max;
+use settings::Settings;
+use random::RandomSource;
+use frame::Fragment;
+use kdf::STRETCH_LEN;
+use util::random_pass;
+
+/// length of the passwords the binary generates, unstretched decoy keys match it
+static PASSWORD_LEN: uint = 24;
+
+/// Decoys are extra pieces under passwords that are thrown away once the blob is written.
+/// nobody can be made to reveal them, so handing over every password in the key file
+/// still doesn't tell how many of the pieces were real
+#[deriving(Clone,PartialEq,Eq,Show)]
+pub enum Decoys {
+    NoDecoys,
+    /// this many, until the first that doesn't fit in the block
+    DecoyCount(uint),
+    /// a random number scaled by how many real items there are
+    DecoyAuto,
+}
+
+impl Decoys {
+    /// parse the N or auto form used by the command line
+    pub fn from_str(s: &str) -> Option<Decoys> {
+        match s {
+            "auto" => Some(DecoyAuto),
+            _      => from_str::<uint>(s).map(|x| if x == 0 { NoDecoys } else { DecoyCount(x) }),
+        }
+    }
+
+    /// how many decoys go alongside items real pieces
+    pub fn count<R:RandomSource>(&self, rng: &mut R, items: uint) -> uint {
+        match *self {
+            NoDecoys      => 0,
+            DecoyCount(x) => x,
+            DecoyAuto     => 1 + rng.below((max(items, 1) * 3) as u32) as uint,
+        }
+    }
+}
+
+/// Decoy is a throwaway key, the content length its piece is made for and the fragment it poses as
+pub struct Decoy {
+    pub key:      Vec<u8>,
+    pub len:      uint,
+    pub fragment: Option<Fragment>,
+}
+
+/// a decoy sized after a random one of the placed pieces, given as the content length and fragment
+/// of each, so neither its length nor its header singles it out among fragments and volume chunks.
+/// decoys are made one at a time as they are placed, so asking for more than fit costs nothing
+pub fn decoy<R:RandomSource>(rng: &mut R, settings: &Settings, placed: &[(uint, Option<Fragment>)]) -> Decoy {
+    let (len, fragment) = match placed.len() {
+        0 => (rng.below(settings.blocksize / 16 + 1) as uint, None),
+        n => {
+            let (template, ref fragment) = placed[rng.below(n as u32) as uint];
+            (template / 2 + rng.below((template * 3 / 2 + 1) as u32) as uint, fragment.clone())
+        },
+    };
+
+    Decoy {
+        key:      throwaway_key(rng, settings),
+        len:      max(len, 1),
+        fragment: fragment,
+    }
+}
+
+/// a key shaped like a real stretched password, nobody ever learns what it unstretches from
+fn throwaway_key<R:RandomSource>(rng: &mut R, settings: &Settings) -> Vec<u8> {
+    match settings.kdf {
+        Some(_) => {
+            let mut key: Vec<u8> = Vec::from_elem(STRETCH_LEN, 0u8);
+            rng.fill(key.as_mut_slice());
+            key
+        },
+        None    => random_pass(rng, PASSWORD_LEN),
+    }
+}
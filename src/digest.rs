@@ -0,0 +1,99 @@
+use std::fmt;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use crypto::sha2::Sha512;
+use crypto::blake2b::Blake2b;
+
+/// DigestKind selects the hash function the keystream is built from.
+/// sha-3 isn't offered as the rust-crypto revision plause builds against has no implementation of it,
+/// blake2b is the choice where sha-2 alone isn't acceptable
+#[deriving(Clone,PartialEq,Eq)]
+pub enum DigestKind {
+    DigestSha256,
+    DigestSha512,
+    DigestBlake2b,
+}
+
+impl DigestKind {
+    pub fn from_name(name: &str) -> Option<DigestKind> {
+        match name {
+            "sha256"  => Some(DigestSha256),
+            "sha512"  => Some(DigestSha512),
+            "blake2b" => Some(DigestBlake2b),
+            _         => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            DigestSha256  => "sha256",
+            DigestSha512  => "sha512",
+            DigestBlake2b => "blake2b",
+        }
+    }
+
+    /// a fresh hasher of this kind
+    pub fn hasher(&self) -> Hasher {
+        match *self {
+            DigestSha256  => HashSha256(Sha256::new()),
+            DigestSha512  => HashSha512(Sha512::new()),
+            DigestBlake2b => HashBlake2b(Blake2b::new(64)),
+        }
+    }
+}
+
+impl fmt::Show for DigestKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Hasher lets the digest be picked at runtime while hash.rs stays generic over Digest.
+/// it is plain data, so a copy carries on from the same state
+pub enum Hasher {
+    HashSha256(Sha256),
+    HashSha512(Sha512),
+    HashBlake2b(Blake2b),
+}
+
+impl Digest for Hasher {
+    fn input(&mut self, input: &[u8]) {
+        match *self {
+            HashSha256(ref mut d)  => d.input(input),
+            HashSha512(ref mut d)  => d.input(input),
+            HashBlake2b(ref mut d) => d.input(input),
+        }
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        match *self {
+            HashSha256(ref mut d)  => d.result(out),
+            HashSha512(ref mut d)  => d.result(out),
+            HashBlake2b(ref mut d) => d.result(out),
+        }
+    }
+
+    fn reset(&mut self) {
+        match *self {
+            HashSha256(ref mut d)  => d.reset(),
+            HashSha512(ref mut d)  => d.reset(),
+            HashBlake2b(ref mut d) => d.reset(),
+        }
+    }
+
+    fn output_bits(&self) -> uint {
+        match *self {
+            HashSha256(ref d)  => d.output_bits(),
+            HashSha512(ref d)  => d.output_bits(),
+            HashBlake2b(ref d) => d.output_bits(),
+        }
+    }
+
+    fn block_size(&self) -> uint {
+        match *self {
+            HashSha256(ref d)  => d.block_size(),
+            HashSha512(ref d)  => d.block_size(),
+            HashBlake2b(ref d) => d.block_size(),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+extern crate "rust-crypto" as crypto;
+
+use std::cmp::max;
+use crypto::digest::Digest;
+use random::RandomSource;
+use settings::Settings;
+use item::Item;
+use piece::max_piece_len;
+use compress::decompress;
+use kdf::stretched_len;
+use volume::{chunk_len, volume_password};
+use fragment::fragment_password;
+use layout::{LayoutScattered, CELL_LEN, CELL_PAYLOAD};
+use error::{PlauseResult, NoSpace};
+
+static OPENINGS: &'static [&'static str] = &[
+    "Didn't sleep much again.",
+    "Quiet day, mostly errands.",
+    "Woke up early for once.",
+    "Long day at work.",
+    "Rained all morning.",
+    "Finally a day off.",
+    "Not much to write about today.",
+    "Felt tired the whole day.",
+];
+
+static EVENTS: &'static [&'static str] = &[
+    "Called mum in the evening and she talked about the garden for an hour.",
+    "The bus was late so I walked the last part.",
+    "Made soup with whatever was left in the fridge, it turned out fine.",
+    "Spent too long reading the news.",
+    "Paid the electricity bill, it was higher than last month.",
+    "Went for a run around the park, only managed two laps.",
+    "Tried to fix the shelf in the hallway, it still wobbles.",
+    "Had coffee with an old friend from school.",
+    "Cleaned the kitchen and sorted out the cupboard under the sink.",
+    "The neighbours were loud again until late.",
+    "Finished the book I started last month.",
+    "Meeting ran long and nothing was decided.",
+    "Bought new shoes, the old ones had a hole.",
+    "Watched a film that everyone talks about, it was alright.",
+    "Forgot my keys and had to wait outside for a while.",
+];
+
+static THOUGHTS: &'static [&'static str] = &[
+    "Should really start saving more.",
+    "I keep meaning to call the dentist.",
+    "Maybe I'll take a trip in the summer.",
+    "Need to get more sleep.",
+    "I wonder if I should look for another job.",
+    "Want to start cooking properly again.",
+    "Feeling a bit better than last week.",
+    "Note to self: buy light bulbs.",
+];
+
+/// a diary starts on one of this many days
+static FIRST_DAYS: uint = 300;
+
+/// share of the free space a duress piece takes, between the two as a percentage
+static MIN_SHARE: uint = 10;
+static MAX_SHARE: uint = 35;
+
+/// a content length that looks natural next to free bytes of unused space in the blob
+pub fn plausible_len<R:RandomSource>(rng: &mut R, free: uint) -> uint {
+    let share = MIN_SHARE + rng.below((MAX_SHARE - MIN_SHARE + 1) as u32) as uint;
+    max(free / 100 * share, 1)
+}
+
+/// the most content each of count more items under password_len byte passwords can hold and still be
+/// sure to fit in every volume beside items. every piece is taken at its longest and the pieces before
+/// it as leaving the free space in as many gaps as they can, so the room is a bound rather than a guess
+pub fn room(settings: &Settings, items: &Vec<Item>, count: uint, password_len: uint) -> PlauseResult<uint> {
+    let sh        = settings.digest.hasher();
+    let split     = settings.fragments >= 2 && settings.framed;
+    let scattered = settings.layout == LayoutScattered;
+    let capacity  = if scattered { settings.blocksize as uint / CELL_LEN } else { settings.blocksize as uint };
+
+    //what the staged items take at most and how many pieces they are at most
+    let mut taken  = 0u;
+    let mut pieces = 0u;
+
+    for item in items.iter() {
+        //volumes and fragments split the content uncompressed, each share compressed on its own
+        let len = match settings.volumes > 1 || split {
+            true  => try!(decompress(item.compression.flags(), item.content.clone())).len(),
+            false => item.content.len(),
+        };
+        let chunk   = chunk_len(len, settings.volumes);
+        let key_len = stretched_len(settings, derived_len(settings, item.password.len()));
+
+        match split {
+            true  => {
+                taken  += range(2, settings.fragments + 1).map(|c| c * extent(&sh, settings, key_len, chunk_len(chunk, c), true)).max().unwrap();
+                pieces += settings.fragments;
+            },
+            false => {
+                taken  += extent(&sh, settings, key_len, chunk, false);
+                pieces += 1;
+            },
+        }
+    }
+
+    //a split item is tried as two fragments first, so those are what have to fit
+    let parts   = if split { 2 } else { 1 };
+    let added   = max(count, 1) * parts;
+    let key_len = stretched_len(settings, derived_len(settings, password_len));
+
+    //lo is the most content known to fit, hi the least known not to
+    let mut lo = 0u;
+    let mut hi = settings.blocksize as uint + 1;
+
+    while hi - lo > 1 {
+        let mid  = lo + (hi - lo) / 2;
+        let need = extent(&sh, settings, key_len, chunk_len(chunk_len(mid, settings.volumes), parts), split);
+
+        let fits = match scattered {
+            //cells are taken wherever they are free, only how many are left matters
+            true  => taken + added * need <= capacity,
+            //the free space is in at most one gap more than there are pieces, the largest at least
+            //their mean, and every added piece both shrinks it and makes another gap
+            false => taken <= capacity && need * (pieces + 2 * added - 1) <= capacity - taken,
+        };
+
+        match fits {
+            true  => lo = mid,
+            false => hi = mid,
+        }
+    }
+
+    match lo {
+        0 => Err(NoSpace(max_piece_len(&sh, settings, key_len, 1, split))),
+        _ => Ok(lo),
+    }
+}
+
+/// length of the longest password one of password_len bytes is derived into for its volumes and fragments
+fn derived_len(settings: &Settings, password_len: uint) -> uint {
+    let password: Vec<u8> = Vec::from_elem(password_len, 0u8);
+    let password = volume_password(&password, settings.volumes - 1, settings.volumes);
+
+    match settings.fragments >= 2 && settings.framed {
+        true  => fragment_password(&password, settings.fragments - 1).len(),
+        false => password.len(),
+    }
+}
+
+/// bytes, or cells when scattered, the longest piece for content_len bytes takes
+fn extent<D:Digest>(sh: &D, settings: &Settings, key_len: uint, content_len: uint, fragment: bool) -> uint {
+    let len = max_piece_len(sh, settings, key_len, content_len, fragment);
+
+    match settings.layout {
+        LayoutScattered => (len + CELL_PAYLOAD - 1) / CELL_PAYLOAD,
+        _               => len,
+    }
+}
+
+/// length of the shortest entry a diary can start with, a diary is never shorter
+pub fn shortest_entry() -> uint {
+    format!("Day {}\n\n\n", FIRST_DAYS).len() + OPENINGS.iter().map(|x| x.len()).min().unwrap()
+}
+
+/// a diary of mundane entries of at most len bytes, believable content to give up under duress.
+/// when not even a short entry fits there is no believable content to give, that is NoSpace
+pub fn diary<R:RandomSource>(rng: &mut R, len: uint) -> PlauseResult<Vec<u8>> {
+    let mut result = String::new();
+    let mut day    = 1u + rng.below(FIRST_DAYS as u32) as uint;
+
+    loop {
+        let mut entry = format!("Day {}\n{}", day, pick(rng, OPENINGS));
+
+        for _ in range(0, 1 + rng.below(3)) {
+            entry.push(' ');
+            entry.push_str(pick(rng, EVENTS));
+        }
+
+        if rng.below(2) == 0 {
+            entry.push(' ');
+            entry.push_str(pick(rng, THOUGHTS));
+        }
+
+        entry.push_str("\n\n");
+
+        //whole entries only, a diary cut off mid sentence would look made up
+        if result.len() + entry.len() > len {
+            if result.is_empty() {
+                return short_entry(rng, day, len);
+            }
+
+            break;
+        }
+
+        result.push_str(entry.as_slice());
+        day += 1 + rng.below(3) as uint;
+    }
+
+    Ok(result.into_bytes())
+}
+
+/// an entry of day holding nothing but an opening that fits in len bytes
+fn short_entry<R:RandomSource>(rng: &mut R, day: uint, len: uint) -> PlauseResult<Vec<u8>> {
+    let entries: Vec<String> = OPENINGS.iter()
+        .map(|x| format!("Day {}\n{}\n\n", day, x))
+        .filter(|x| x.len() <= len)
+        .collect();
+
+    match entries.len() {
+        0 => Err(NoSpace(shortest_entry())),
+        n => Ok(entries[rng.below(n as u32) as uint].clone().into_bytes()),
+    }
+}
+
+fn pick<R:RandomSource>(rng: &mut R, from: &[&'static str]) -> &'static str {
+    from[rng.below(from.len() as u32) as uint]
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use std::error::Error;
+use std::error::FromError;
+use std::io::IoError;
+
+/// PlauseError covers everything that can go wrong while embedding or extracting
+pub enum PlauseError {
+    /// no prefix needle for the password exists in the blob
+    NotFound,
+    /// a prefix needle was found but no postfix needle follows it
+    Unterminated,
+    /// the piece's mac didn't verify, it was tampered with or belongs to another password
+    IntegrityError,
+    /// no gap in the block can hold a piece of this length
+    NoSpace(uint),
+    /// item length and blocksize, the item can never fit
+    OversizeItem(uint, u32),
+    /// the password is already used by a staged item
+    DuplicatePassword,
+    /// the password would be read back from a key file as something else
+    InvalidPassword,
+    /// the key file could not be understood
+    MalformedKeyFile(String),
+    /// the piece is one fragment of an item, the rest can only be found in a seekable blob
+    FragmentedPiece,
+    Io(IoError),
+}
+
+pub type PlauseResult<T> = Result<T, PlauseError>;
+
+impl fmt::Show for PlauseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NotFound                 => write!(f, "no piece found for password"),
+            Unterminated             => write!(f, "piece found for password but its end is missing"),
+            IntegrityError           => write!(f, "piece failed verification, the blob is damaged or the password is wrong"),
+            NoSpace(len)             => write!(f, "couldn't find non overlapping segment of {} bytes, try increasing blocksize", len),
+            OversizeItem(len, size)  => write!(f, "size of file({}) is bigger than blocksize({})", len, size),
+            DuplicatePassword        => write!(f, "using the same password for multiple inputs allows an agent to see where datablocks begin/end.. don't do it."),
+            InvalidPassword          => write!(f, "passwords must be utf-8 without line breaks and can't start with '!', or the key file can't hold them"),
+            MalformedKeyFile(ref s)  => write!(f, "malformed key file: {}", s),
+            FragmentedPiece          => write!(f, "item is split into fragments, extract it from a file rather than a stream"),
+            Io(ref e)                => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for PlauseError {
+    fn description(&self) -> &str {
+        match *self {
+            NotFound               => "piece not found",
+            Unterminated           => "piece unterminated",
+            IntegrityError         => "piece failed verification",
+            NoSpace(..)            => "no space left in block",
+            OversizeItem(..)       => "item bigger than blocksize",
+            DuplicatePassword      => "duplicate password",
+            InvalidPassword        => "invalid password",
+            MalformedKeyFile(..)   => "malformed key file",
+            FragmentedPiece        => "fragmented piece",
+            Io(ref e)              => e.description(),
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            Io(ref e) => Some(e as &Error),
+            _         => None,
+        }
+    }
+}
+
+impl FromError<IoError> for PlauseError {
+    fn from_error(err: IoError) -> PlauseError {
+        Io(err)
+    }
+}
@@ -0,0 +1,35 @@
+use item::Item;
+use compress::decompress;
+use error::PlauseResult;
+use util::concat_vec;
+
+/// password of fragment seq of an item, the first fragment uses the item's own password
+/// so extraction finds it without knowing the item was split
+pub fn fragment_password(password: &Vec<u8>, seq: uint) -> Vec<u8> {
+    match seq {
+        0 => password.clone(),
+        _ => concat_vec(password.clone(), format!("/{}", seq).into_bytes()),
+    }
+}
+
+/// item split into count fragments under their derived passwords, earlier fragments take
+/// the remainder. like volume chunks, a compressed item is split uncompressed and each
+/// fragment compressed again
+pub fn fragment_items(item: &Item, count: uint) -> PlauseResult<Vec<Item>> {
+    let content = try!(decompress(item.compression.flags(), item.content.clone()));
+    let base    = content.len() / count;
+    let extra   = content.len() % count;
+
+    let mut result: Vec<Item> = Vec::with_capacity(count);
+    let mut start = 0u;
+
+    for seq in range(0, count) {
+        let len      = base + if seq < extra { 1 } else { 0 };
+        let password = fragment_password(&item.password, seq);
+
+        result.push(Item::compressed(&password, &content[start..start + len].to_vec(), item.compression.clone()));
+        start += len;
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,264 @@
+use std::cmp::min;
+use std::io::{Writer, IoResult};
+use error::{PlauseResult, IntegrityError, FragmentedPiece};
+use compress::{decompress, block_len, inflate_block, FLAG_DEFLATE, BLOCK_HEADER};
+
+/// bytes at the start of a framed piece body: a flags byte then the content length,
+/// whatever follows the content is padding
+pub static HEADER_LEN: uint = 9;
+
+/// header flag marking a fragment of an item, the header then goes on with its sequence
+/// number and the item's fragment count
+pub static FLAG_FRAGMENT: u8 = 0x02;
+
+/// bytes the fragment flag adds to the header
+pub static FRAGMENT_LEN: uint = 4;
+
+/// every flag this release understands, deflate and fragment, pieces with any other set are rejected
+static KNOWN_FLAGS: u8 = 0x03;
+
+/// Fragment is the place of a piece among the pieces an item was split into
+#[deriving(Clone,PartialEq,Eq,Show)]
+pub struct Fragment {
+    pub seq:   uint,
+    pub total: uint,
+}
+
+/// Frame is the content of a decrypted framed body
+pub struct Frame {
+    pub content:  Vec<u8>,
+    pub fragment: Option<Fragment>,
+}
+
+/// the header of a piece holding len bytes of content
+pub fn header(flags: u8, len: u64, fragment: &Option<Fragment>) -> Vec<u8> {
+    let mut result: Vec<u8> = vec![flags | if fragment.is_some() { FLAG_FRAGMENT } else { 0 }];
+    result.extend(range(0u, 8).map(|i| (len >> (56 - 8 * i)) as u8));
+
+    match *fragment {
+        Some(ref x) => result.push_all([(x.seq >> 8) as u8, x.seq as u8, (x.total >> 8) as u8, x.total as u8]),
+        None        => { },
+    }
+
+    result
+}
+
+/// length of the header of a piece, fragment or not
+pub fn header_len(fragment: bool) -> uint {
+    HEADER_LEN + if fragment { FRAGMENT_LEN } else { 0 }
+}
+
+/// how long the header starting with flags is, None for flags this release doesn't understand
+fn flags_header_len(flags: u8) -> Option<uint> {
+    match flags & !KNOWN_FLAGS {
+        0 => Some(header_len(flags & FLAG_FRAGMENT != 0)),
+        _ => None,
+    }
+}
+
+/// flags, content length and fragment from a complete header
+fn parse_header(header: &[u8]) -> (u8, u64, Option<Fragment>) {
+    let flags = header[0];
+    let len   = header[1..HEADER_LEN].iter().fold(0u64, |acc, &x| (acc << 8) | x as u64);
+
+    let fragment = match flags & FLAG_FRAGMENT {
+        0 => None,
+        _ => Some(Fragment {
+            seq:   (header[HEADER_LEN]     as uint << 8) | header[HEADER_LEN + 1] as uint,
+            total: (header[HEADER_LEN + 2] as uint << 8) | header[HEADER_LEN + 3] as uint,
+        }),
+    };
+
+    (flags, len, fragment)
+}
+
+/// length of the header and content of a decrypted framed body from as much of its start as
+/// holds the header, None when it doesn't or for flags this release doesn't understand
+pub fn framed_len(start: &[u8]) -> Option<u64> {
+    match start.head().and_then(|&x| flags_header_len(x)) {
+        Some(x) if x <= start.len() => {
+            let (_, len, _) = parse_header(start);
+            Some(x as u64 + len)
+        },
+        _                          => None,
+    }
+}
+
+/// whether a header's fragment could have been written, one of at least two
+fn valid_fragment(fragment: &Option<Fragment>) -> bool {
+    match *fragment {
+        Some(ref x) => x.total >= 2 && x.seq < x.total,
+        None        => true,
+    }
+}
+
+/// the content of a decrypted framed body, without its header and padding and decompressed
+pub fn unframe(body: &[u8]) -> PlauseResult<Frame> {
+    let hlen = match body.head().and_then(|&x| flags_header_len(x)) {
+        Some(x) if x <= body.len() => x,
+        _                          => return Err(IntegrityError),
+    };
+
+    let (flags, len, fragment) = parse_header(body);
+
+    if len > (body.len() - hlen) as u64 || !valid_fragment(&fragment) {
+        return Err(IntegrityError);
+    }
+
+    Ok(Frame {
+        content:  try!(decompress(flags, body[hlen..hlen + len as uint].to_vec())),
+        fragment: fragment,
+    })
+}
+
+/// FrameWriter passes a decrypted framed body through to writer as it is streamed,
+/// keeping the header back and dropping the padding. compressed content is held
+/// a deflated block at a time and written out as each one is inflated
+pub struct FrameWriter<'a, W: 'a> {
+    writer:    &'a mut W,
+    header:    Vec<u8>,
+    /// length of the header, known once its flags are
+    hlen:      uint,
+    remaining: u64,
+    fragment:  Option<Fragment>,
+    /// the deflated block being read, when the content is compressed
+    held:      Option<Vec<u8>>,
+    /// the header has flags this release doesn't understand or a deflated block is damaged
+    refused:   bool,
+    /// the rest of a fragmented item can't be gone back for, so a fragment is refused
+    whole:     bool,
+}
+
+impl<'a, W: Writer> FrameWriter<'a, W> {
+    pub fn new(writer: &'a mut W) -> FrameWriter<'a, W> {
+        FrameWriter {
+            writer:    writer,
+            header:    Vec::with_capacity(HEADER_LEN + FRAGMENT_LEN),
+            hlen:      HEADER_LEN,
+            remaining: 0,
+            fragment:  None,
+            held:      None,
+            refused:   false,
+            whole:     false,
+        }
+    }
+
+    /// a FrameWriter for a stream that can't go back for the rest of an item, a piece
+    /// that turns out to be a fragment is refused before any of its content is written
+    pub fn whole(writer: &'a mut W) -> FrameWriter<'a, W> {
+        let mut frame = FrameWriter::new(writer);
+        frame.whole = true;
+        frame
+    }
+
+    /// check the body held all the content its header promised and write out anything held back,
+    /// returning which fragment of its item the piece was
+    pub fn finish(self) -> PlauseResult<Option<Fragment>> {
+        if self.whole && self.fragment.is_some() {
+            return Err(FragmentedPiece);
+        }
+
+        if self.refused || self.header.len() != self.hlen || self.remaining != 0 {
+            return Err(IntegrityError);
+        }
+
+        //compressed content must end with a whole block
+        match self.held {
+            Some(ref x) if !x.is_empty() => return Err(IntegrityError),
+            _                            => { },
+        }
+
+        Ok(self.fragment)
+    }
+
+    /// add deflated content to the block being held, writing out every block it completes inflated
+    fn inflate(&mut self, buf: &[u8]) -> IoResult<()> {
+        let mut buf = buf;
+
+        let held = match self.held {
+            Some(ref mut x) => x,
+            None            => return Ok(()),
+        };
+
+        while !buf.is_empty() {
+            //the block header first, then as much of the block as it gives
+            let want = match block_len(held.as_slice()) {
+                Some(len)                          => BLOCK_HEADER + len,
+                None if held.len() < BLOCK_HEADER => {
+                    let take = min(BLOCK_HEADER - held.len(), buf.len());
+                    held.push_all(buf[..take]);
+                    buf = buf[take..];
+                    continue;
+                },
+                None                               => {
+                    self.refused = true;
+                    return Ok(());
+                },
+            };
+
+            let take = min(want - held.len(), buf.len());
+            held.push_all(buf[..take]);
+            buf = buf[take..];
+
+            if held.len() < want {
+                continue;
+            }
+
+            match inflate_block(held[BLOCK_HEADER..]) {
+                Ok(content) => try!(self.writer.write(content.as_slice())),
+                Err(_)      => {
+                    self.refused = true;
+                    return Ok(());
+                },
+            }
+
+            held.clear();
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, W: Writer> Writer for FrameWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        let mut buf = buf;
+
+        while self.header.len() < self.hlen && !buf.is_empty() && !self.refused {
+            let take = min(self.hlen - self.header.len(), buf.len());
+            self.header.push_all(buf[..take]);
+            buf = buf[take..];
+
+            //the flags byte says how much header follows it
+            match flags_header_len(self.header[0]) {
+                Some(x) => self.hlen = x,
+                None    => self.refused = true,
+            }
+
+            if self.header.len() == self.hlen {
+                let (flags, len, fragment) = parse_header(self.header.as_slice());
+                self.remaining = len;
+                self.fragment  = fragment;
+
+                if !valid_fragment(&self.fragment) || (self.whole && self.fragment.is_some()) {
+                    self.refused = true;
+                }
+
+                if flags & FLAG_DEFLATE != 0 {
+                    self.held = Some(Vec::with_capacity(BLOCK_HEADER));
+                }
+            }
+        }
+
+        if self.refused {
+            return Ok(());
+        }
+
+        let take = min(self.remaining, buf.len() as u64) as uint;
+        self.remaining -= take as u64;
+
+        match self.held {
+            Some(_) => self.inflate(buf[..take]),
+            None    => self.writer.write(buf[..take]),
+        }
+    }
+}
@@ -2,72 +2,141 @@ extern crate "rust-crypto" as crypto;
 
 use crypto::digest::Digest;
 use settings::Settings;
-use util::concat_vec;
+use util::{concat_vec, fixed_time_eq};
+use keystream::Keystream;
 
-static CASCADE_ROUNDS: uint = 16;
+/// xor content with a fresh copy of keystream
+pub fn hxor(keystream: &Keystream, content: &[u8]) -> Vec<u8> {
+    let mut result = content.to_vec();
+    keystream.clone().apply(result.as_mut_slice());
+    result
+}
 
-pub fn hxor<D:Digest>(sh: &mut D, settings: &Settings, password: &Vec<u8>, content: &Vec<u8>) -> Vec<u8> {
-    let init = concat_vec(settings.salt.clone(), password.clone()); 
-    let mut cascade: Vec<Vec<u8>> = Vec::from_fn(CASCADE_ROUNDS, |x| hash(&mut *sh, &concat_vec(init.clone(), vec![(x + 1) as u8])));
-    let hlen = cascade[0].len();
-    let mut result: Vec<u8> = Vec::new();
+pub fn prefix_ident(keystream: &Keystream, settings: &Settings, password: &Vec<u8>) -> Vec<u8> {
+    ident(keystream, settings, &settings.salt_prefix, password)
+}
 
-    for (i, c) in content.as_slice().iter().enumerate() {
-        let hmod = i % hlen;
+pub fn postfix_ident(keystream: &Keystream, settings: &Settings, password: &Vec<u8>) -> Vec<u8> {
+    ident(keystream, settings, &settings.salt_postfix, password)
+}
 
-        if hmod == 0 {
-            for (i, v) in cascade.clone().iter().enumerate() {
-                *cascade.get_mut(i) = hash(&mut *sh, &concat_vec(settings.salt.clone(), v.clone()));
-            }
-        }
+/// length of either ident of a password key_len bytes long
+pub fn ident_len(settings: &Settings, key_len: uint) -> uint {
+    match settings.ident_len {
+        Some(len) => len,
+        None      => settings.salt_prefix.len() + key_len,
+    }
+}
 
-        result.push(cascade.iter().fold(c.clone(), |acc, it| acc ^ it[hmod]));
+/// salt || password under the keystream, or with a fixed ident length a digest expansion of it
+/// so the ident says nothing about how long the password is
+fn ident(keystream: &Keystream, settings: &Settings, salt: &Vec<u8>, password: &Vec<u8>) -> Vec<u8> {
+    let seed = concat_vec(salt.clone(), password.clone());
+
+    let len = match settings.ident_len {
+        Some(x) => x,
+        None    => return hxor(keystream, seed.as_slice()),
+    };
+
+    let mut sh = settings.digest.hasher();
+    let mut material: Vec<u8> = Vec::with_capacity(len);
+    let mut counter = 0u32;
+
+    while material.len() < len {
+        let block: Vec<u8> = Vec::from_fn(4, |i| (counter >> (24 - 8 * i)) as u8);
+        material.push_all(hash(&mut sh, true, &concat_vec(seed.clone(), block)).as_slice());
+        counter += 1;
     }
 
-    result
+    material.truncate(len);
+    hxor(keystream, material.as_slice())
 }
 
-pub fn prefix_ident<D:Digest>(sh: &mut D, settings: &Settings, password: &Vec<u8>) -> Vec<u8> {
-    hxor(sh, settings, password, &concat_vec(settings.salt_prefix.clone(), password.clone()))
+/// hmac over content keyed by the password, built on whichever digest sh is
+pub fn mac<D:Digest>(sh: &mut D, settings: &Settings, password: &Vec<u8>, content: &[u8]) -> Vec<u8> {
+    let mut hmac = Hmac::new(sh, settings, password);
+    hmac.input(content);
+    hmac.result()
 }
 
-pub fn postfix_ident<D:Digest>(sh: &mut D, settings: &Settings, password: &Vec<u8>) -> Vec<u8> {
-    hxor(sh, settings, password, &concat_vec(settings.salt_postfix.clone(), password.clone()))
+/// Hmac computes mac incrementally for content that is never held in memory at once
+pub struct Hmac<'a, D: 'a> {
+    sh:   &'a mut D,
+    opad: Vec<u8>,
 }
 
-pub fn hash<D:Digest>(sh: &mut D, input: &Vec<u8>) -> Vec<u8> {
-    let mut result: Vec<u8> = Vec::new();
+impl<'a, D:Digest> Hmac<'a, D> {
+    pub fn new(sh: &'a mut D, settings: &Settings, password: &Vec<u8>) -> Hmac<'a, D> {
+        let key = hash(sh, settings.raw_hash, &concat_vec(settings.salt_mac.clone(), password.clone()));
+        let block_size = sh.block_size();
 
-    //perform hashing
-    sh.input_str(input.iter().map(|x| x.clone() as char).collect::<String>().as_slice());
-    let hash = sh.result_str();
-    let h = hash.as_slice();
-    sh.reset();
+        let mut key = if key.len() > block_size { digest(sh, key.as_slice()) } else { key };
+        key.grow(block_size - key.len(), 0u8);
 
-    //populate result
-    let mut last = 0u8;
-    let mut i    = 0u32;
+        let ipad: Vec<u8> = key.iter().map(|x| x ^ 0x36).collect();
+        let opad: Vec<u8> = key.iter().map(|x| x ^ 0x5c).collect();
 
-    for c in h.chars() {
-        let cur = chash_to_u8(c);
+        sh.reset();
+        sh.input(ipad.as_slice());
 
-        if i % 2 == 0 {
-            last = cur;
-        } else {
-            result.push((last * 16) + cur);
+        Hmac {
+            sh:   sh,
+            opad: opad,
         }
+    }
 
-        i += 1;
+    pub fn input(&mut self, content: &[u8]) {
+        self.sh.input(content);
     }
 
-    result
+    pub fn result(self) -> Vec<u8> {
+        let sh    = self.sh;
+        let inner = result(sh);
+
+        sh.input(self.opad.as_slice());
+        sh.input(inner.as_slice());
+        result(sh)
+    }
 }
 
-fn chash_to_u8(c: char) -> u8 {
-    match c {
-        '0'=>0, '1'=>1, '2'=>2, '3'=>3, '4'=>4, '5'=>5, '6'=>6, '7'=>7, '8'=>8, '9'=>9,
-        'a'=>10, 'b'=>11, 'c'=>12, 'd'=>13, 'e'=>14, 'f'=>15,
-         _ =>fail!("exceeded [0-9][a-f]"),
+impl<'a, D:Digest + Copy> Hmac<'a, D> {
+    /// whether the content so far followed by tail has mac tag, the state is left as it was
+    /// so a tag that doesn't match can turn out to be more content
+    pub fn check(&self, tail: &[u8], tag: &[u8]) -> bool {
+        let mut sh    = *self.sh;
+        let mut trial = Hmac {
+            sh:   &mut sh,
+            opad: self.opad.clone(),
+        };
+
+        trial.input(tail);
+        fixed_time_eq(trial.result().as_slice(), tag)
     }
 }
 
+/// length of the tag mac produces
+pub fn mac_len<D:Digest>(sh: &D) -> uint {
+    sh.output_bytes()
+}
+
+fn digest<D:Digest>(sh: &mut D, input: &[u8]) -> Vec<u8> {
+    sh.reset();
+    sh.input(input);
+    result(sh)
+}
+
+fn result<D:Digest>(sh: &mut D) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::from_elem(sh.output_bytes(), 0u8);
+    sh.result(out.as_mut_slice());
+    sh.reset();
+    out
+}
+
+/// digest of input. raw hashing feeds the bytes straight to the digest, otherwise every byte
+/// is widened to a char first as the original format did, bytes from 0x80 up hashing as two
+pub fn hash<D:Digest>(sh: &mut D, raw: bool, input: &Vec<u8>) -> Vec<u8> {
+    match raw {
+        true  => digest(sh, input.as_slice()),
+        false => digest(sh, input.iter().map(|&x| x as char).collect::<String>().as_bytes()),
+    }
+}
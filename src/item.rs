@@ -1,14 +1,32 @@
+use compress::{Compression, NoCompression};
+
+/// Item is a password and the content it unlocks, staged for embedding
 #[deriving(Clone,Show)]
 pub struct Item {
-    pub password:  Vec<u8>,
-    pub content:   Vec<u8>,
+    pub password:    Vec<u8>,
+    /// stored already compressed as compression says
+    pub content:     Vec<u8>,
+    pub compression: Compression,
 }
 
 impl Item {
     pub fn new(password: &Vec<u8>, content: &Vec<u8>) -> Item {
         Item {
-            password: password.clone(),
-            content:  content.clone(),
+            password:    password.clone(),
+            content:     content.clone(),
+            compression: NoCompression,
+        }
+    }
+
+    /// an item for content compressed as compression says, kept uncompressed if that doesn't shrink it
+    pub fn compressed(password: &Vec<u8>, content: &Vec<u8>, compression: Compression) -> Item {
+        match compression.compress(content.as_slice()) {
+            Some(packed) => Item {
+                password:    password.clone(),
+                content:     packed,
+                compression: compression,
+            },
+            None         => Item::new(password, content),
         }
     }
 }
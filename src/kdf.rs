@@ -0,0 +1,120 @@
+extern crate time;
+
+use std::fmt;
+use crypto::scrypt::scrypt;
+use crypto::scrypt::ScryptParams;
+use settings::Settings;
+
+/// length of a stretched password
+pub static STRETCH_LEN: uint = 32;
+
+static CALIBRATE_PASS:  &'static [u8] = b"plause-calibration";
+static MIN_LOG_N:       u8 = 10;
+
+/// most memory stretching a password may take, key files and the command line asking for more are
+/// refused so a hostile key file can't exhaust the machine
+pub static MAX_MEMORY: u64 = 1 << 30;
+
+/// most mixing passes stretching a password may run, each one takes as long as log_n and r ask for
+pub static MAX_PARALLEL: u32 = 16;
+
+/// KdfParams are the scrypt costs passwords are stretched with before
+/// they are used for the keystream or the idents
+#[deriving(Clone,PartialEq,Eq)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r:     u32,
+    pub p:     u32,
+}
+
+impl KdfParams {
+    pub fn new(log_n: u8, r: u32, p: u32) -> KdfParams {
+        KdfParams {
+            log_n: log_n,
+            r:     r,
+            p:     p,
+        }
+    }
+
+    /// the costs new blobs are generated with unless told otherwise
+    pub fn default() -> KdfParams {
+        KdfParams::new(14, 8, 1)
+    }
+
+    /// parse the LOGN:R:P form used by the key file and the command line
+    pub fn from_str(s: &str) -> Option<KdfParams> {
+        let parts: Vec<&str> = s.split(':').collect();
+
+        if parts.len() != 3 {
+            return None;
+        }
+
+        match (from_str::<u8>(parts[0]), from_str::<u32>(parts[1]), from_str::<u32>(parts[2])) {
+            (Some(log_n), Some(r), Some(p)) if KdfParams::new(log_n, r, p).is_valid() => Some(KdfParams::new(log_n, r, p)),
+            _ => None,
+        }
+    }
+
+    /// whether scrypt accepts these costs, log_n below 16 * r and r * p below 2^30, p is at most
+    /// MAX_PARALLEL and the 128 * r * (2^log_n + p) bytes they take stay within MAX_MEMORY
+    pub fn is_valid(&self) -> bool {
+        let r = self.r as u64;
+        let p = self.p as u64;
+
+        //checked in order, the scratch buffer fitting keeps the full sum from overflowing
+        self.log_n > 0 && self.log_n < 64 && r > 0 && p > 0
+            && self.p <= MAX_PARALLEL
+            && (self.log_n as u64) < 16 * r
+            && r * p < 1 << 30
+            && 128 * r <= MAX_MEMORY >> self.log_n as uint
+            && 128 * r * ((1 << self.log_n as uint) + p) <= MAX_MEMORY
+    }
+
+    pub fn stretch(&self, salt: &[u8], password: &[u8]) -> Vec<u8> {
+        let mut result: Vec<u8> = Vec::from_elem(STRETCH_LEN, 0u8);
+        scrypt(password, salt, &ScryptParams::new(self.log_n, self.r, self.p), result.as_mut_slice());
+        result
+    }
+
+    /// pick the smallest cost that takes at least target_ms to unlock one password on this machine,
+    /// or the largest within MAX_MEMORY if none does
+    pub fn calibrate(target_ms: u64) -> KdfParams {
+        let mut params = KdfParams::new(MIN_LOG_N, 8, 1);
+
+        loop {
+            let start = time::precise_time_ns();
+            params.stretch(CALIBRATE_PASS, CALIBRATE_PASS);
+            let elapsed_ms = (time::precise_time_ns() - start) / 1000000;
+
+            let next = KdfParams::new(params.log_n + 1, params.r, params.p);
+
+            if elapsed_ms >= target_ms || !next.is_valid() {
+                return params;
+            }
+
+            params = next;
+        }
+    }
+}
+
+impl fmt::Show for KdfParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.log_n, self.r, self.p)
+    }
+}
+
+/// length of a password_len byte password once stretch has been applied
+pub fn stretched_len(settings: &Settings, password_len: uint) -> uint {
+    match settings.kdf {
+        Some(_) => STRETCH_LEN,
+        None    => password_len,
+    }
+}
+
+/// the password as it enters the keystream, stretched if settings ask for it
+pub fn stretch(settings: &Settings, password: &Vec<u8>) -> Vec<u8> {
+    match settings.kdf {
+        Some(ref params) => params.stretch(settings.salt.as_slice(), password.as_slice()),
+        None             => password.clone(),
+    }
+}
@@ -0,0 +1,284 @@
+use std::str;
+use std::io::Writer;
+use std::io::IoResult;
+use std::io::{IoError, InvalidInput};
+use std::io::Buffer;
+use settings::{Settings, FORMAT_VERSION, valid_ident_len};
+use digest::{DigestKind, DigestSha256};
+use kdf::KdfParams;
+use keystream::{KeystreamKind, KeystreamCascade};
+use layout::{Layout, LayoutContiguous};
+use error::{PlauseResult, MalformedKeyFile};
+
+/// lines following the salt that begin with this describe how the blob was generated,
+/// generated passwords are alphanumeric so they can never start with it
+static OPTION_MARKER: u8 = b'!';
+
+/// KeyFile holds everything needed to extract pieces from a blob:
+/// the salt on the first line, then option lines, then one password per line.
+/// a version option comes first, the options after it override what that version defaults to
+#[deriving(Clone,Show)]
+pub struct KeyFile {
+    pub salt:      Vec<u8>,
+    pub version:   u32,
+    pub digest:    DigestKind,
+    pub keystream: KeystreamKind,
+    pub raw_hash:  bool,
+    pub ident_len: Option<uint>,
+    pub kdf:       Option<KdfParams>,
+    pub mac:       bool,
+    pub volumes:   uint,
+    pub layout:    Layout,
+    pub passwords: Vec<Vec<u8>>,
+}
+
+impl KeyFile {
+    pub fn new(settings: &Settings, passwords: &Vec<Vec<u8>>) -> KeyFile {
+        KeyFile {
+            salt:      settings.salt.clone(),
+            version:   settings.version,
+            digest:    settings.digest.clone(),
+            keystream: settings.keystream.clone(),
+            raw_hash:  settings.raw_hash,
+            ident_len: settings.ident_len,
+            kdf:       settings.kdf.clone(),
+            mac:       settings.authenticate,
+            volumes:   settings.volumes,
+            layout:    settings.layout.clone(),
+            passwords: passwords.clone(),
+        }
+    }
+
+    /// parse a key file, the first line being the salt and every following line an option or password
+    pub fn read<B: Buffer>(reader: &mut B) -> PlauseResult<KeyFile> {
+        let mut keyfile = KeyFile {
+            salt:      Vec::new(),
+            version:   0,
+            digest:    DigestSha256,
+            keystream: KeystreamCascade,
+            raw_hash:  false,
+            ident_len: None,
+            kdf:       None,
+            mac:       false,
+            volumes:   1,
+            layout:    LayoutContiguous,
+            passwords: Vec::new(),
+        };
+        let mut enough_lines: bool = false;
+        let mut first_option: bool = true;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = strip_newline(try!(line).into_bytes());
+
+            if i == 0 {
+                keyfile.salt = line;
+            } else if line.len() > 0 && line[0] == OPTION_MARKER {
+                try!(keyfile.read_option(line.tail(), first_option));
+                first_option = false;
+            } else {
+                enough_lines = true;
+                keyfile.passwords.push(line);
+            }
+        }
+
+        if !enough_lines {
+            return Err(MalformedKeyFile(String::from_str("password file requires at least two lines (salt) and (password(s))")));
+        }
+
+        Ok(keyfile)
+    }
+
+    pub fn write<W: Writer>(&self, writer: &mut W) -> IoResult<()> {
+        //nothing is written unless every password reads back as itself
+        if !self.passwords.iter().all(|x| valid_password(x)) {
+            return Err(IoError {
+                kind:   InvalidInput,
+                desc:   "password can't be stored in a key file",
+                detail: None,
+            });
+        }
+
+        try!(writer.write(self.salt.as_slice()));
+        try!(writer.write_line(""));
+
+        //options matching the original format are left out so older releases can still read the file
+        if self.version != 0 {
+            try!(write_option(writer, "version", self.version.to_string().as_slice()));
+        }
+
+        if self.digest != DigestSha256 {
+            try!(write_option(writer, "digest", self.digest.name()));
+        }
+
+        //the rest are only written where they differ from what the version implies
+        let defaults = version_settings(self.version);
+
+        if self.keystream != defaults.keystream {
+            try!(write_option(writer, "keystream", self.keystream.name()));
+        }
+
+        if self.raw_hash != defaults.raw_hash {
+            try!(write_option(writer, "hash", if self.raw_hash { "raw" } else { "char" }));
+        }
+
+        if self.ident_len != defaults.ident_len {
+            match self.ident_len {
+                Some(len) => try!(write_option(writer, "ident", len.to_string().as_slice())),
+                None      => try!(write_option(writer, "ident", "variable")),
+            }
+        }
+
+        match self.kdf {
+            Some(ref params) => try!(write_option(writer, "kdf", format!("scrypt:{}", params).as_slice())),
+            None             => { },
+        }
+
+        if self.mac {
+            try!(write_option(writer, "mac", "hmac"));
+        }
+
+        if self.volumes != 1 {
+            try!(write_option(writer, "volumes", self.volumes.to_string().as_slice()));
+        }
+
+        if self.layout != LayoutContiguous {
+            try!(write_option(writer, "layout", self.layout.name()));
+        }
+
+        for i in self.passwords.iter() {
+            try!(writer.write(i.as_slice()));
+            try!(writer.write_line(""));
+        }
+
+        Ok(())
+    }
+
+    /// configure settings so they match those the blob was generated with
+    pub fn apply(&self, settings: &mut Settings) {
+        settings.set_salt(self.salt.clone());
+        settings.set_version(self.version);
+        settings.digest    = self.digest.clone();
+        settings.keystream = self.keystream.clone();
+        settings.raw_hash  = self.raw_hash;
+        settings.ident_len = self.ident_len;
+        settings.kdf       = self.kdf.clone();
+        settings.authenticate = self.mac;
+        settings.volumes      = self.volumes;
+        settings.layout       = self.layout.clone();
+    }
+
+    fn read_option(&mut self, option: &[u8], first: bool) -> PlauseResult<()> {
+        let option = match str::from_utf8(option) {
+            Some(x) => x,
+            None    => return Err(MalformedKeyFile(String::from_str("option is not valid utf-8"))),
+        };
+
+        let (key, value) = match option.find('=') {
+            Some(x) => (option[..x], option[x + 1..]),
+            None    => return Err(MalformedKeyFile(format!("option '{}' has no value", option))),
+        };
+
+        match key {
+            "version" => {
+                if !first {
+                    return Err(MalformedKeyFile(String::from_str("version must come before every other option")));
+                }
+
+                self.version = match from_str::<u32>(value) {
+                    Some(x) if x <= FORMAT_VERSION => x,
+                    Some(x) => return Err(MalformedKeyFile(format!("format version {} is newer than this release understands", x))),
+                    None    => return Err(MalformedKeyFile(format!("invalid version '{}'", value))),
+                };
+
+                let defaults = version_settings(self.version);
+                self.keystream = defaults.keystream;
+                self.raw_hash  = defaults.raw_hash;
+                self.ident_len = defaults.ident_len;
+            },
+            "digest" => {
+                self.digest = match DigestKind::from_name(value) {
+                    Some(x) => x,
+                    None    => return Err(MalformedKeyFile(format!("unknown digest '{}'", value))),
+                };
+            },
+            "keystream" => {
+                self.keystream = match KeystreamKind::from_name(value) {
+                    Some(x) => x,
+                    None    => return Err(MalformedKeyFile(format!("unknown keystream '{}'", value))),
+                };
+            },
+            "hash" => {
+                self.raw_hash = match value {
+                    "raw"  => true,
+                    "char" => false,
+                    _      => return Err(MalformedKeyFile(format!("unknown hash encoding '{}'", value))),
+                };
+            },
+            "ident" => {
+                self.ident_len = match (value, from_str::<uint>(value)) {
+                    ("variable", _)                    => None,
+                    (_, Some(x)) if valid_ident_len(x) => Some(x),
+                    _                                  => return Err(MalformedKeyFile(format!("invalid ident length '{}'", value))),
+                };
+            },
+            "kdf" => {
+                self.kdf = match value.starts_with("scrypt:") {
+                    true  => KdfParams::from_str(value["scrypt:".len()..]),
+                    false => None,
+                };
+
+                if self.kdf.is_none() {
+                    return Err(MalformedKeyFile(format!("unknown kdf '{}'", value)));
+                }
+            },
+            "mac" => {
+                if value != "hmac" {
+                    return Err(MalformedKeyFile(format!("unknown mac '{}'", value)));
+                }
+
+                self.mac = true;
+            },
+            "volumes" => {
+                self.volumes = match from_str::<uint>(value) {
+                    Some(x) if x > 0 => x,
+                    _                => return Err(MalformedKeyFile(format!("invalid volume count '{}'", value))),
+                };
+            },
+            "layout" => {
+                self.layout = match Layout::from_name(value) {
+                    Some(x) => x,
+                    None    => return Err(MalformedKeyFile(format!("unknown layout '{}'", value))),
+                };
+            },
+            _ => return Err(MalformedKeyFile(format!("unknown option '{}'", key))),
+        }
+
+        Ok(())
+    }
+}
+
+/// settings holding the structural choices of format version
+fn version_settings(version: u32) -> Settings {
+    let mut settings = Settings::new(0, "");
+    settings.set_version(version);
+    settings
+}
+
+/// whether password reads back from a key file unchanged, a line of utf-8 that isn't an option
+pub fn valid_password(password: &Vec<u8>) -> bool {
+    str::from_utf8(password.as_slice()).is_some()
+        && !password.iter().any(|&x| x == b'\n')
+        && password.as_slice().head() != Some(&OPTION_MARKER)
+}
+
+fn write_option<W: Writer>(writer: &mut W, key: &str, value: &str) -> IoResult<()> {
+    try!(writer.write_u8(OPTION_MARKER));
+    writer.write_line(format!("{}={}", key, value).as_slice())
+}
+
+fn strip_newline(line: Vec<u8>) -> Vec<u8> {
+    match line.last() {
+        Some(&b'\n') => line.init().to_vec(),
+        _            => line,
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+use std::os;
+use std::sync::Future;
+use std::slice::bytes::copy_memory;
+use crypto::digest::Digest;
+use settings::Settings;
+use digest::DigestKind;
+use hash::hash;
+use util::concat_vec;
+
+static CASCADE_ROUNDS: uint = 16;
+
+/// runs shorter than this aren't worth spreading over threads
+static PARALLEL_THRESHOLD: uint = 1048576;
+
+/// KeystreamKind selects how the keystream xored over pieces is generated
+#[deriving(Clone,PartialEq,Eq)]
+pub enum KeystreamKind {
+    /// the original chained cascade, every block depends on the one before it
+    KeystreamCascade,
+    /// one digest of the key and block counter per block, seekable and generated in parallel
+    KeystreamCounter,
+}
+
+impl KeystreamKind {
+    pub fn from_name(name: &str) -> Option<KeystreamKind> {
+        match name {
+            "cascade" => Some(KeystreamCascade),
+            "counter" => Some(KeystreamCounter),
+            _         => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            KeystreamCascade => "cascade",
+            KeystreamCounter => "counter",
+        }
+    }
+}
+
+impl fmt::Show for KeystreamKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[deriving(Clone)]
+enum Source {
+    /// salt, whether hashing is raw, the cascade before its first rehash and the cascade of the loaded block
+    CascadeSource(Vec<u8>, bool, Vec<Vec<u8>>, Vec<Vec<u8>>),
+    /// digest of salt and password every block is derived from
+    CounterSource(Vec<u8>),
+}
+
+/// Keystream is built once per (salt, password) and cloned wherever that password's
+/// keystream is needed again, the idents and content all start from the same state
+#[deriving(Clone)]
+pub struct Keystream {
+    digest: DigestKind,
+    source: Source,
+    /// index of the block held in block
+    index:  uint,
+    block:  Vec<u8>,
+    offset: uint,
+}
+
+impl Keystream {
+    pub fn new(settings: &Settings, password: &Vec<u8>) -> Keystream {
+        let mut sh = settings.digest.hasher();
+        let init   = concat_vec(settings.salt.clone(), password.clone());
+
+        let source = match settings.keystream {
+            KeystreamCascade => {
+                let cascade = Vec::from_fn(CASCADE_ROUNDS, |x| hash(&mut sh, settings.raw_hash, &concat_vec(init.clone(), vec![(x + 1) as u8])));
+                CascadeSource(settings.salt.clone(), settings.raw_hash, cascade.clone(), cascade)
+            },
+            KeystreamCounter => {
+                let mut key: Vec<u8> = Vec::from_elem(sh.output_bytes(), 0u8);
+                sh.input(init.as_slice());
+                sh.result(key.as_mut_slice());
+                CounterSource(key)
+            },
+        };
+
+        let mut keystream = Keystream {
+            digest: settings.digest.clone(),
+            source: source,
+            index:  0,
+            block:  Vec::new(),
+            offset: 0,
+        };
+
+        keystream.load(0);
+        keystream
+    }
+
+    /// continue from offset bytes into the keystream, only the counter keystream does this without
+    /// regenerating everything before it
+    pub fn seek(&mut self, offset: uint) {
+        self.offset = offset;
+    }
+
+    /// xor the next buf.len() bytes of keystream into buf
+    pub fn apply(&mut self, buf: &mut [u8]) {
+        let parallel = match self.source {
+            CounterSource(..) => buf.len() >= PARALLEL_THRESHOLD && os::num_cpus() > 1,
+            CascadeSource(..) => false,
+        };
+
+        match parallel {
+            true  => self.apply_parallel(buf),
+            false => self.apply_serial(buf),
+        }
+    }
+
+    fn apply_serial(&mut self, buf: &mut [u8]) {
+        let hlen = self.block.len();
+
+        for c in buf.iter_mut() {
+            let index = self.offset / hlen;
+
+            if index != self.index {
+                self.load(index);
+            }
+
+            *c ^= self.block[self.offset % hlen];
+            self.offset += 1;
+        }
+    }
+
+    /// split buf into a run per cpu, each xored by its own copy of the keystream seeked to its start
+    fn apply_parallel(&mut self, buf: &mut [u8]) {
+        let hlen = self.block.len();
+
+        //runs are whole blocks so no block is generated twice
+        let run = (buf.len() / os::num_cpus() / hlen + 1) * hlen;
+
+        let futures: Vec<Future<Vec<u8>>> = buf.chunks(run).enumerate().map(|(i, chunk)| {
+            let mut keystream = self.clone();
+            let mut chunk     = chunk.to_vec();
+
+            keystream.seek(self.offset + i * run);
+
+            Future::spawn(proc() {
+                keystream.apply_serial(chunk.as_mut_slice());
+                chunk
+            })
+        }).collect();
+
+        for (i, future) in futures.into_iter().enumerate() {
+            copy_memory(buf[mut i * run..], future.unwrap().as_slice());
+        }
+
+        self.offset += buf.len();
+    }
+
+    /// generate block index into block
+    fn load(&mut self, index: uint) {
+        let mut sh = self.digest.hasher();
+        let reload = self.block.is_empty() || index < self.index;
+
+        let block = match self.source {
+            CascadeSource(ref salt, raw, ref init, ref mut cascade) => {
+                //the cascade only runs forwards, going back means starting over
+                let mut rounds = if reload { 0 } else { self.index + 1 };
+
+                if reload {
+                    *cascade = init.clone();
+                }
+
+                while rounds <= index {
+                    for v in cascade.iter_mut() {
+                        *v = hash(&mut sh, raw, &concat_vec(salt.clone(), v.clone()));
+                    }
+
+                    rounds += 1;
+                }
+
+                Vec::from_fn(cascade[0].len(), |i| cascade.iter().fold(0u8, |acc, it| acc ^ it[i]))
+            },
+            CounterSource(ref key) => {
+                let counter: Vec<u8> = Vec::from_fn(8, |i| ((index as u64) >> (56 - 8 * i)) as u8);
+                let mut block: Vec<u8> = Vec::from_elem(sh.output_bytes(), 0u8);
+
+                sh.input(key.as_slice());
+                sh.input(counter.as_slice());
+                sh.result(block.as_mut_slice());
+                block
+            },
+        };
+
+        self.index = index;
+        self.block = block;
+    }
+}
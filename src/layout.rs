@@ -0,0 +1,225 @@
+extern crate "rust-crypto" as crypto;
+
+use std::fmt;
+use std::cmp::{max, min};
+use std::io::{Reader, Seek, SeekSet, IoResult};
+use crypto::digest::Digest;
+use settings::Settings;
+use alloc::Allocator;
+use hash::hash;
+use util::concat_vec;
+
+static CELL_SALT:   &'static [u8] = b"5c@773r3dC3ll$";
+static MARKER_SALT: &'static [u8] = b"c3llM@rk3r";
+
+/// rounds of the feistel network, enough to spread neighbouring positions over the whole domain
+static FEISTEL_ROUNDS: uint = 4;
+
+/// bytes of a cell in a scattered block, a marker followed by a share of one piece
+pub static CELL_LEN: uint = 64;
+
+/// bytes at the start of every cell a scattered piece takes, only its key recognises them.
+/// long enough that no filler or cell of another piece ever passes for one of its own
+pub static MARKER_LEN: uint = 16;
+
+/// bytes of a piece held by one cell, CELL_LEN less the marker
+pub static CELL_PAYLOAD: uint = 48;
+
+/// Layout decides how a piece's bytes map onto the block
+#[deriving(Clone,PartialEq,Eq)]
+pub enum Layout {
+    /// every piece is one run of consecutive bytes
+    LayoutContiguous,
+    /// pieces are runs of consecutive bytes starting where their password says, so extraction
+    /// reads a few candidate offsets instead of scanning. the starts follow a keyed sequence,
+    /// to anyone without the password they are as uniform as random placement
+    LayoutDerived,
+    /// the block is cut into cells and every piece takes the first free ones along a sequence
+    /// its key permutes, so its bytes are spread over the whole block in an order only its password
+    /// can follow. the whole block is held in memory to write it, reading follows the sequence
+    LayoutScattered,
+}
+
+impl Layout {
+    pub fn from_name(name: &str) -> Option<Layout> {
+        match name {
+            "contiguous" => Some(LayoutContiguous),
+            "derived"    => Some(LayoutDerived),
+            "scattered"  => Some(LayoutScattered),
+            _            => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            LayoutContiguous => "contiguous",
+            LayoutDerived    => "derived",
+            LayoutScattered  => "scattered",
+        }
+    }
+}
+
+impl fmt::Show for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Permutation is a keyed shuffle of 0..size, a feistel network cycle walked down to size
+#[deriving(Clone)]
+pub struct Permutation {
+    size:      u32,
+    /// bits in each half of the network's domain, the smallest even power of two holding size
+    half_bits: uint,
+    keys:      Vec<u32>,
+}
+
+impl Permutation {
+    /// a permutation keyed by the salt and key, purpose keeps the ones made for different uses apart
+    pub fn new(settings: &Settings, purpose: &[u8], key: &[u8], size: u32) -> Permutation {
+        let mut bits = 2u;
+
+        while bits < 32 && (1u64 << bits) < size as u64 {
+            bits += 2;
+        }
+
+        let mut sh = settings.digest.hasher();
+        let seed   = concat_vec(concat_vec(purpose.to_vec(), settings.salt.clone()), key.to_vec());
+        let digest = hash(&mut sh, true, &seed);
+
+        Permutation {
+            size:      size,
+            half_bits: bits / 2,
+            keys:      Vec::from_fn(FEISTEL_ROUNDS, |i| digest[i * 4..i * 4 + 4].iter().fold(0u32, |acc, &x| (acc << 8) | x as u32)),
+        }
+    }
+
+    /// number of positions permuted
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// where pos goes, pos must be below the size
+    pub fn apply(&self, pos: u32) -> u32 {
+        let mut x = pos;
+
+        //the network permutes a power of two, stepping again until back inside
+        //keeps it a permutation of 0..size
+        loop {
+            x = self.feistel(x);
+
+            if x < self.size {
+                return x;
+            }
+        }
+    }
+
+    fn feistel(&self, x: u32) -> u32 {
+        let mask      = (1u32 << self.half_bits) - 1;
+        let mut left  = x >> self.half_bits;
+        let mut right = x & mask;
+
+        for &key in self.keys.iter() {
+            let next = left ^ (round(right, key) & mask);
+            left  = right;
+            right = next;
+        }
+
+        (left << self.half_bits) | right
+    }
+}
+
+/// a cheap mix of x under key, the content is already encrypted so this only has to scatter
+fn round(x: u32, key: u32) -> u32 {
+    let h = (x ^ key) as u64 * 0x9E3779B97F4A7C15;
+    (h >> 32) as u32 ^ h as u32
+}
+
+/// the cells of a size byte block in the order key's piece may take them
+fn sequence(settings: &Settings, key: &Vec<u8>, size: uint) -> Permutation {
+    Permutation::new(settings, CELL_SALT, key.as_slice(), (size / CELL_LEN) as u32)
+}
+
+/// marker of key in cell, it tells the key's own cells from those of pieces placed before it
+fn marker<D:Digest>(sh: &mut D, key: &Vec<u8>, cell: u32) -> Vec<u8> {
+    let index: Vec<u8> = Vec::from_fn(4, |i| (cell >> (24 - 8 * i)) as u8);
+    let mut result = hash(sh, true, &concat_vec(concat_vec(MARKER_SALT.to_vec(), key.clone()), index));
+
+    result.truncate(MARKER_LEN);
+    result
+}
+
+/// reserve the cells for a piece of len bytes under key, the first free ones along its sequence
+pub fn reserve(settings: &Settings, alloc: &mut Allocator, key: &Vec<u8>, len: uint) -> Option<Vec<u32>> {
+    let needed   = (len + CELL_PAYLOAD - 1) / CELL_PAYLOAD;
+    let sequence = sequence(settings, key, settings.blocksize as uint);
+    let mut cells: Vec<u32> = Vec::with_capacity(needed);
+
+    for i in range(0, sequence.size()) {
+        if cells.len() == needed {
+            break;
+        }
+
+        let cell = sequence.apply(i);
+
+        if alloc.reserve(cell * CELL_LEN as u32, CELL_LEN as u32) {
+            cells.push(cell);
+        }
+    }
+
+    match cells.len() == needed {
+        true  => Some(cells),
+        false => None,
+    }
+}
+
+/// write the piece of key held in stream over its cells of block
+pub fn scatter(settings: &Settings, key: &Vec<u8>, cells: &[u32], stream: &[u8], block: &mut [u8]) {
+    let mut sh = settings.digest.hasher();
+
+    for (&cell, chunk) in cells.iter().zip(stream.chunks(CELL_PAYLOAD)) {
+        let start = cell as uint * CELL_LEN;
+
+        block[mut start..start + MARKER_LEN].copy_from(marker(&mut sh, key, cell).as_slice());
+        block[mut start + MARKER_LEN..start + MARKER_LEN + chunk.len()].copy_from(chunk);
+    }
+}
+
+/// the piece of key in a seekable blob of size bytes, every cell along its sequence that carries
+/// its marker in order. cells of pieces placed before it are passed over, and no cell is read
+/// past the one completing its postfix ident. a blob without the piece has its whole sequence
+/// walked, a marker at a time
+pub fn gather<R:Reader + Seek>(reader: &mut R, settings: &Settings, key: &Vec<u8>, size: u64, prefix: &[u8], postfix: &[u8]) -> IoResult<Vec<u8>> {
+    let mut sh     = settings.digest.hasher();
+    let sequence   = sequence(settings, key, size as uint);
+    let mut result: Vec<u8> = Vec::new();
+
+    for i in range(0, sequence.size()) {
+        let cell = sequence.apply(i);
+
+        try!(reader.seek((cell as uint * CELL_LEN) as i64, SeekSet));
+
+        if try!(reader.read_exact(MARKER_LEN)) != marker(&mut sh, key, cell) {
+            continue;
+        }
+
+        let from = result.len();
+        result.push_all(try!(reader.read_exact(CELL_PAYLOAD)).as_slice());
+
+        //the piece's first cell starts with its prefix ident, anything else isn't its piece
+        let head = min(result.len(), prefix.len());
+
+        if result[..head] != prefix[..head] {
+            break;
+        }
+
+        //a postfix ident straddling the previous cell is searched for too
+        let search = max(prefix.len(), if from >= postfix.len() { from + 1 - postfix.len() } else { 0 });
+
+        if search < result.len() && result[search..].windows(postfix.len()).any(|x| x == postfix) {
+            break;
+        }
+    }
+
+    Ok(result)
+}
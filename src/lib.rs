@@ -0,0 +1,65 @@
+//! plause encrypts and embeds pieces of data into a blob of random data so
+//! that it is computationally infeasible to know how many pieces are in the
+//! blob, where they are, or what they contain.
+//!
+//! ```ignore
+//! let mut plause = try!(Plause::new(1048576, "saltysaltsalt"));
+//! let password = plause.random_pass(24);
+//! try!(plause.add(&password, &content));
+//! try!(plause.gen());
+//!
+//! let mut reader = try!(Plause::new(1048576, "saltysaltsalt"));
+//! try!(reader.import(&mut MemReader::new(plause.content.clone())));
+//! let original = try!(reader.extract(&password));
+//! ```
+
+#![crate_name = "plause"]
+#![crate_type = "lib"]
+#![feature(slicing_syntax)]
+
+extern crate "rust-crypto" as crypto;
+
+pub use plause::Plause;
+pub use settings::Settings;
+pub use item::Item;
+pub use keyfile::KeyFile;
+pub use random::RandomSource;
+pub use util::random_pass;
+pub use error::PlauseError;
+pub use error::PlauseResult;
+pub use digest::DigestKind;
+pub use kdf::KdfParams;
+pub use keystream::KeystreamKind;
+pub use padding::Padding;
+pub use decoy::Decoys;
+pub use compress::Compression;
+pub use layout::Layout;
+pub use reader::BlobReader;
+pub use reader::Location;
+pub use writer::BlobWriter;
+
+mod hash;
+mod util;
+mod piece;
+mod frame;
+mod probe;
+pub mod error;
+pub mod matcher;
+pub mod digest;
+pub mod random;
+pub mod kdf;
+pub mod keystream;
+pub mod padding;
+pub mod decoy;
+pub mod duress;
+pub mod compress;
+pub mod volume;
+pub mod fragment;
+pub mod layout;
+pub mod reader;
+pub mod writer;
+pub mod alloc;
+pub mod item;
+pub mod settings;
+pub mod keyfile;
+pub mod plause;
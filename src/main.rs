@@ -1,7 +1,7 @@
 #![crate_type="bin"]
 #![feature(slicing_syntax)]
 
-extern crate "rust-crypto" as crypto;
+extern crate plause;
 extern crate getopts;
 
 use std::os;
@@ -9,35 +9,62 @@ use std::from_str::FromStr;
 use std::io;
 use std::io::File;
 use std::io::BufferedReader;
+use std::io::BufferedWriter;
+use std::io::fs;
+use std::io::fs::PathExtensions;
 use getopts::{optopt,optflag,optmulti,getopts,OptGroup};
-use util::random_pass;
 use plause::Plause;
-
-mod hash;
-mod util;
-mod item;
-mod piece;
-mod block;
-mod settings;
-mod plause;
+use plause::KeyFile;
+use plause::RandomSource;
+use plause::PlauseResult;
+use plause::DigestKind;
+use plause::KdfParams;
+use plause::KeystreamKind;
+use plause::Padding;
+use plause::Decoys;
+use plause::Compression;
+use plause::Layout;
+use plause::Settings;
+use plause::settings::{FORMAT_VERSION, DEFAULT_IDENT_LEN, MIN_IDENT_LEN, MAX_IDENT_LEN, valid_ident_len};
+use plause::volume;
 
 static VERSION:                             f32 = 0.01;
 
 static DEFAULT_ENCRYPTFILE:        &'static str = "output.enc";
 static DEFAULT_DECRYPTFILE:        &'static str = "output.dec";
 static DEFAULT_PASSWORDECRYPTFILE: &'static str = "pass.key";
+static DEFAULT_DURESSFILE:         &'static str = "duress.key";
 static DEFAULT_SALT:               &'static str = "saltysaltsalt";
+static DEFAULT_DIGEST:             &'static str = "sha256";
+static DEFAULT_KEYSTREAM:          &'static str = "counter";
 static DEFAULT_BLOCKSIZE:                   u32 = 1048576;
 
 static CONT_ENCRYPT_FILE:     &'static str = "E";
 static CONT_PASSWORD_FILE:    &'static str = "P";
 static CONT_SET_BLOCKSIZE:    &'static str = "b";
 static CONT_SET_SALT:         &'static str = "s";
+static CONT_SET_DIGEST:       &'static str = "H";
+static CONT_SET_KEYSTREAM:    &'static str = "keystream";
+static CONT_SET_IDENT_LEN:    &'static str = "ident-len";
+static CONT_SET_PADDING:      &'static str = "pad";
+static CONT_SET_DECOYS:       &'static str = "decoys";
+static CONT_SET_COMPRESSION:  &'static str = "compress";
+static CONT_SET_VOLUMES:      &'static str = "volumes";
+static CONT_SET_FRAGMENTS:    &'static str = "fragments";
+static CONT_SET_LAYOUT:       &'static str = "layout";
+static CONT_SET_KDF:          &'static str = "k";
+static CONT_KDF_CALIBRATE:    &'static str = "kdf-calibrate";
 static CONT_HELP_MODE:        &'static str = "h";
 static CONT_VERSION_MODE:     &'static str = "v";
 static CONT_DECRYPT_MODE:     &'static str = "d";
 static CONT_ENCRYPT_MODE:     &'static str = "e";
 static CONT_INTERACTIVE_MODE: &'static str = "i";
+static CONT_SET_SEED:         &'static str = "seed";
+static CONT_DURESS_FILE:      &'static str = "duress-file";
+static CONT_DECOY_DIR:        &'static str = "decoy-dir";
+
+static CMD_MIGRATE:           &'static str = "migrate";
+static CMD_DECOY:             &'static str = "decoy";
 
 #[deriving(PartialEq, Eq)]
 pub enum Mode {
@@ -46,6 +73,8 @@ pub enum Mode {
     Interactive,
     Encrypt,
     Decrypt,
+    Migrate,
+    Decoy,
 }
 
 fn main() {
@@ -56,8 +85,22 @@ fn main() {
         optopt(CONT_ENCRYPT_FILE,  "encrypt-file",  format!("set encryption output file (default: {})", DEFAULT_ENCRYPTFILE).as_slice(), "FILENAME"),
         optopt(CONT_PASSWORD_FILE, "password-file", format!("set password file (default: {})", DEFAULT_PASSWORDECRYPTFILE).as_slice(),   "FILENAME"),
         
+        optopt("", CONT_DURESS_FILE,   format!("set duress password file written by decoy (default: {})", DEFAULT_DURESSFILE).as_slice(), "FILENAME"),
+        optopt("", CONT_DECOY_DIR,     "embed the files in DIR as the duress content instead of a generated diary", "DIR"),
+        
         optopt(CONT_SET_BLOCKSIZE, "blocksize", format!("set block size (default: {})", DEFAULT_BLOCKSIZE).as_slice(), "SIZE"),
         optopt(CONT_SET_SALT,      "salt",      format!("set salt (default: {})", DEFAULT_SALT).as_slice(),            "SALT"),
+        optopt(CONT_SET_DIGEST,    "digest",    format!("set keystream digest sha256, sha512 or blake2b (default: {})", DEFAULT_DIGEST).as_slice(), "NAME"),
+        optopt("", CONT_SET_KEYSTREAM,          format!("set keystream counter or cascade, cascade being the original slower one (default: {})", DEFAULT_KEYSTREAM).as_slice(), "NAME"),
+        optopt("", CONT_SET_IDENT_LEN,          format!("set length of the idents marking piece boundaries (default: {})", DEFAULT_IDENT_LEN).as_slice(), "LEN"),
+        optopt("", CONT_SET_PADDING,            "pad pieces so their size isn't revealed: none, pow2, buckets:LEN,LEN,.. or random:PERCENT up to 100 (default: none)", "POLICY"),
+        optopt("", CONT_SET_DECOYS,             "add N decoy pieces whose passwords are thrown away, or auto for a random number (default: 0)", "N|auto"),
+        optopt("", CONT_SET_COMPRESSION,        "compress items before embedding with deflate or none (default: none)", "NAME"),
+        optopt("", CONT_SET_VOLUMES,            "split every input across N blobs of blocksize each, written as FILENAME.0..N (default: 1)", "N"),
+        optopt("", CONT_SET_FRAGMENTS,          "split every input into up to N fragments placed apart within the blob (default: 1)", "N"),
+        optopt("", CONT_SET_LAYOUT,             "lay pieces out contiguous, derived from their password or scattered over the whole block (default: contiguous)", "NAME"),
+        optopt(CONT_SET_KDF,       "kdf",       format!("set scrypt password stretching costs or none (default: {})", KdfParams::default()).as_slice(), "LOGN:R:P"),
+        optopt("", CONT_KDF_CALIBRATE,          "pick scrypt costs so unlocking a password takes about MILLIS", "MILLIS"),
 
         optflag(CONT_HELP_MODE,        "help",        "print this help menu"),
         optflag(CONT_VERSION_MODE,     "version",     "show version"),
@@ -66,37 +109,212 @@ fn main() {
         optopt(CONT_DECRYPT_MODE,      "decrypt",     "decrypt file using password-file and filename prefix for output", "FILENAME"),
     ];
 
-    let matches = match getopts(args.tail(), opts) {
+    //debugging options, accepted but left out of the usage text
+    let mut all_opts = opts.to_vec();
+    all_opts.push(optopt("", CONT_SET_SEED, "seed the random source for reproducible output", "SEED"));
+
+    let matches = match getopts(args.tail(), all_opts.as_slice()) {
         Ok(m)  => { m },
-        Err(f) => { fail!(f.to_string()) },
+        Err(f) => {
+            report_error(f.to_string());
+            return;
+        },
     };
 
     let blocksize     = fallback_match_fstr(CONT_SET_BLOCKSIZE, &matches, DEFAULT_BLOCKSIZE);
     let salt          = fallback_match(CONT_SET_SALT,           &matches, DEFAULT_SALT);
-    let encrypt_file  = fallback_match(CONT_ENCRYPT_FILE,       &matches, DEFAULT_ENCRYPTFILE);
-    let password_file = fallback_match(CONT_PASSWORD_FILE,      &matches, DEFAULT_PASSWORDECRYPTFILE);
+    let digest        = fallback_match(CONT_SET_DIGEST,         &matches, DEFAULT_DIGEST);
+    let keystream     = fallback_match(CONT_SET_KEYSTREAM,      &matches, DEFAULT_KEYSTREAM);
+
+    let mut settings = Settings::new(blocksize, salt.as_slice());
+
+    settings.digest = match DigestKind::from_name(digest.as_slice()) {
+        Some(x) => x,
+        None    => {
+            report_error(format!("unknown digest: {}", digest));
+            return;
+        },
+    };
+
+    settings.keystream = match KeystreamKind::from_name(keystream.as_slice()) {
+        Some(x) => x,
+        None    => {
+            report_error(format!("unknown keystream: {}", keystream));
+            return;
+        },
+    };
+
+    settings.ident_len = match matches.opt_str(CONT_SET_IDENT_LEN) {
+        Some(x) => match from_str::<uint>(x.as_slice()) {
+            Some(len) if valid_ident_len(len) => Some(len),
+            _                                 => {
+                report_error(format!("invalid ident length: {}, it must be from {} to {}", x, MIN_IDENT_LEN, MAX_IDENT_LEN));
+                return;
+            },
+        },
+        None    => Some(DEFAULT_IDENT_LEN),
+    };
+
+    match matches.opt_str(CONT_SET_PADDING) {
+        Some(x) => match Padding::from_str(x.as_slice()) {
+            Some(padding) => settings.padding = padding,
+            None          => {
+                report_error(format!("invalid padding: {}", x));
+                return;
+            },
+        },
+        None    => { },
+    }
+
+    match matches.opt_str(CONT_SET_DECOYS) {
+        Some(x) => match Decoys::from_str(x.as_slice()) {
+            Some(decoys) => settings.decoys = decoys,
+            None         => {
+                report_error(format!("invalid decoy count: {}", x));
+                return;
+            },
+        },
+        None    => { },
+    }
+
+    match matches.opt_str(CONT_SET_COMPRESSION) {
+        Some(x) => match Compression::from_name(x.as_slice()) {
+            Some(compression) => settings.compression = compression,
+            None              => {
+                report_error(format!("unknown compression: {}", x));
+                return;
+            },
+        },
+        None    => { },
+    }
+
+    settings.volumes = match matches.opt_str(CONT_SET_VOLUMES) {
+        Some(x) => match from_str::<uint>(x.as_slice()) {
+            Some(count) if count > 0 => count,
+            _                        => {
+                report_error(format!("invalid volume count: {}", x));
+                return;
+            },
+        },
+        None    => 1,
+    };
+
+    match matches.opt_str(CONT_SET_LAYOUT) {
+        Some(x) => match Layout::from_name(x.as_slice()) {
+            Some(layout) => settings.layout = layout,
+            None         => {
+                report_error(format!("unknown layout: {}", x));
+                return;
+            },
+        },
+        None    => { },
+    }
+
+    //the fragment count is stored in two header bytes
+    settings.fragments = match matches.opt_str(CONT_SET_FRAGMENTS) {
+        Some(x) => match from_str::<uint>(x.as_slice()) {
+            Some(count) if count > 0 && count <= 65535 => count,
+            _                                          => {
+                report_error(format!("invalid fragment count: {}", x));
+                return;
+            },
+        },
+        None    => 1,
+    };
+
+    settings.kdf = match matches.opt_str(CONT_SET_KDF) {
+        Some(ref x) if x.as_slice() == "none" => None,
+        Some(x) => match KdfParams::from_str(x.as_slice()) {
+            Some(params) => Some(params),
+            None         => {
+                report_error(format!("invalid kdf costs: {}", x));
+                return;
+            },
+        },
+        None    => Some(KdfParams::default()),
+    };
+
+    match matches.opt_str(CONT_KDF_CALIBRATE) {
+        Some(x) => match from_str::<u64>(x.as_slice()) {
+            Some(ms) => {
+                let params = KdfParams::calibrate(ms);
+                println!("calibrated kdf costs: {}", params);
+                settings.kdf = Some(params);
+            },
+            None     => {
+                report_error(format!("invalid calibration time: {}", x));
+                return;
+            },
+        },
+        None    => { },
+    }
+
+    //a mistyped seed must not quietly fall back to a blob nobody can reproduce
+    let seed = match matches.opt_str(CONT_SET_SEED) {
+        Some(x) => match from_str::<u64>(x.as_slice()) {
+            Some(seed) => Some(seed),
+            None       => {
+                report_error(format!("invalid seed: {}", x));
+                return;
+            },
+        },
+        None    => None,
+    };
+
+    let result = match get_mode(&matches) {
+        Help             => { print_usage(program.as_slice(), opts); Ok(()) },
+        Version          => { print_version(program.as_slice()); Ok(()) },
+        mode             => match seed {
+            Some(seed) => run(&mut Plause::with_seed(blocksize, salt.as_slice(), seed), mode, settings, &matches),
+            None       => match Plause::new(blocksize, salt.as_slice()) {
+                Ok(mut plause) => run(&mut plause, mode, settings, &matches),
+                Err(e)         => Err(e),
+            },
+        },
+    };
+
+    match result {
+        Ok(())  => { },
+        Err(e)  => report_error(e.to_string()),
+    }
+}
 
-    let mut plause = Plause::new(blocksize, salt.as_slice());
+fn run<R: RandomSource>(plause: &mut Plause<R>, mode: Mode, settings: Settings, matches: &getopts::Matches) -> PlauseResult<()> {
+    let encrypt_file  = fallback_match(CONT_ENCRYPT_FILE,  matches, DEFAULT_ENCRYPTFILE);
+    let password_file = fallback_match(CONT_PASSWORD_FILE, matches, DEFAULT_PASSWORDECRYPTFILE);
 
-    match get_mode(&matches) {
-        Help             => print_usage(program.as_slice(), opts),
-        Version          => print_version(program.as_slice()),
-        Interactive      => encrypt_interactive(&mut plause,
+    plause.settings = settings;
+
+    match mode {
+        Interactive      => encrypt_interactive(plause,
                                                 &Path::new(encrypt_file.as_slice()),
-                                                &Path::new(password_file.as_slice()),
-                                                salt.as_slice()),
-        Encrypt          => encrypt_files(&mut plause,
+                                                &Path::new(password_file.as_slice())),
+        Encrypt          => encrypt_files(plause,
                                           &Path::new(encrypt_file.as_slice()),
                                           &Path::new(password_file.as_slice()),
-                                          salt.as_slice(),
                                           &matches.opt_strs(CONT_ENCRYPT_MODE).iter().map(|x| Path::new(x.as_slice())).collect::<Vec<Path>>()),
-        Decrypt          => decrypt(&mut plause,
+        Decrypt          => decrypt(plause,
                                     &Path::new(encrypt_file.as_slice()),
                                     &Path::new(matches.opt_str(CONT_DECRYPT_MODE).unwrap_or(String::from_str(DEFAULT_DECRYPTFILE))),
                                     &Path::new(password_file.as_slice())),
+        Migrate          => migrate(plause,
+                                    &Path::new(encrypt_file.as_slice()),
+                                    &Path::new(password_file.as_slice())),
+        Decoy            => encrypt_duress(plause,
+                                           &Path::new(encrypt_file.as_slice()),
+                                           &Path::new(password_file.as_slice()),
+                                           &Path::new(fallback_match(CONT_DURESS_FILE, matches, DEFAULT_DURESSFILE)),
+                                           &matches.opt_strs(CONT_ENCRYPT_MODE).iter().map(|x| Path::new(x.as_slice())).collect::<Vec<Path>>(),
+                                           matches.opt_str(CONT_DECOY_DIR).map(|x| Path::new(x))),
+        Help | Version   => Ok(()),
     }
 }
 
+fn report_error(msg: String) {
+    let _ = writeln!(&mut io::stderr(), "error: {}", msg);
+    os::set_exit_status(1);
+}
+
 fn print_usage(program: &str, opts: &[OptGroup]) {
     println!("{}", getopts::short_usage(program.as_slice(), opts));
     println!("plausible deniability for love and friendship");
@@ -107,9 +325,43 @@ fn print_usage(program: &str, opts: &[OptGroup]) {
     println!("\t\t\tencrypt FILE1 and FILE2 into output.enc of 120kb size")
     println!("\t\t\tusing generated passwords secretsaltysalt as salt");
     println!("\t\t\tsaving them into pass.key");
+    println!("    ./plause -H sha512 -eFILE1");
+    println!("\t\t\tencrypt FILE1 using a sha-512 keystream, the digest");
+    println!("\t\t\tis recorded in pass.key for decryption");
+    println!("    ./plause --pad pow2 -eFILE1 -eFILE2");
+    println!("\t\t\tencrypt FILE1 and FILE2 padding each piece to a power");
+    println!("\t\t\tof two so their exact sizes aren't revealed");
+    println!("    ./plause --compress deflate --pad pow2 -eFILE1");
+    println!("\t\t\tencrypt FILE1 deflated, padded so the compressed size");
+    println!("\t\t\tdoesn't hint at what kind of file it was");
+    println!("    ./plause --volumes 3 -eFILE1");
+    println!("\t\t\tsplit FILE1 across output.enc.0, output.enc.1 and");
+    println!("\t\t\toutput.enc.2, unlocked by a single password");
+    println!("    ./plause --fragments 4 -eFILE1");
+    println!("\t\t\tencrypt FILE1 as two to four pieces scattered through");
+    println!("\t\t\toutput.enc so it never shows as one long run");
+    println!("    ./plause --layout scattered -eFILE1 -eFILE2");
+    println!("\t\t\tencrypt FILE1 and FILE2 with their bytes spread over");
+    println!("\t\t\tthe whole of output.enc, the layout is saved in pass.key");
+    println!("    ./plause --layout derived -eFILE1");
+    println!("\t\t\tencrypt FILE1 at an offset derived from its password so");
+    println!("\t\t\tdecrypting reads a few offsets instead of the whole blob");
+    println!("    ./plause --decoys auto -eFILE1");
+    println!("\t\t\tencrypt FILE1 among a random number of decoy pieces");
+    println!("\t\t\twhose passwords are never saved");
+    println!("    ./plause --kdf-calibrate 500 -eFILE1");
+    println!("\t\t\tencrypt FILE1 with scrypt costs taking about half a");
+    println!("\t\t\tsecond per password to unlock");
     println!("    ./plause -d output_files");
     println!("\t\t\tDecrypt output.enc to output_files.# with salt/passwords");
     println!("\t\t\tfrom pass.key");
+    println!("    ./plause decoy -eFILE1");
+    println!("\t\t\tencrypt FILE1 alongside a generated diary whose");
+    println!("\t\t\tpassword is saved to duress.key, to be revealed");
+    println!("\t\t\tunder pressure in place of pass.key");
+    println!("    ./plause migrate");
+    println!("\t\t\tre-encode output.enc and pass.key made by an older");
+    println!("\t\t\trelease into the current format, keeping the passwords");
     println!("");
     println!("report all bugs to github.com/whackashoe/plause");
 }
@@ -118,68 +370,174 @@ fn print_version(program: &str) {
     println!("{} {}", program, VERSION);
 }
 
-fn decrypt(plause: &mut Plause, encrypt_path: &Path, decrypt_path: &Path, password_path: &Path) {
-    let mut passwords: Vec<Vec<u8>> = Vec::new();
-    let mut enough_lines: bool = false;
-
-    match File::open(password_path) {
-        Ok(f)  => {
-            let mut file = BufferedReader::new(f);
-            for (i, line) in file.lines().enumerate() {
-                if i == 0 {
-                    plause.set_salt(line.unwrap().into_bytes().init().to_vec());
-                } else {
-                    enough_lines = true;
-                    passwords.push(line.unwrap().into_bytes().init().to_vec());
-                }
-            }
+fn decrypt<R: RandomSource>(plause: &mut Plause<R>, encrypt_path: &Path, decrypt_path: &Path, password_path: &Path) -> PlauseResult<()> {
+    let keyfile = try!(KeyFile::read(&mut BufferedReader::new(try!(File::open(password_path)))));
 
-            if !enough_lines {
-                fail!("password file requires at least two lines (salt) and (password(s))");
-            }
-        },
-        Err(e) => fail!("decrypt open password path: {}", e),
-    }
+    plause.set_keyfile(&keyfile);
 
-    match File::open(encrypt_path).read_to_end() {
-        Ok(f)  => plause.import(f),
-        Err(e) => fail!("decrypt open encrypt path: {}", e),
-    }
+    let volumes = plause.settings.volumes;
+    let mut decpaths: Vec<Path> = Vec::new();
+    let mut outputs:  Vec<Option<BufferedWriter<File>>> = Vec::new();
 
-    for (i, password) in passwords.iter().enumerate() {
-        let decpath = if passwords.len() == 1 {
+    for i in range(0, keyfile.passwords.len()) {
+        let decpath = if keyfile.passwords.len() == 1 {
             decrypt_path.clone()
         } else {
             Path::new(format!("{}.{}", decrypt_path.display(), i))
         };
-        match File::create(&decpath).write(plause.extract(password).as_slice()) {
-            Ok(f)  => f,
-            Err(e) => fail!("decrypt create decrypt path: {}", e),
-        };
+
+        outputs.push(Some(BufferedWriter::new(try!(File::create(&decpath)))));
+        decpaths.push(decpath);
+    }
+
+    //every volume holds a share of each item, appended to its output in volume order
+    let mut blobs: Vec<File> = Vec::new();
+
+    for v in range(0, volumes) {
+        blobs.push(try!(File::open(&volume::volume_path(encrypt_path, v, volumes))));
+    }
+
+    let results = try!(plause.extract_volumes(blobs.as_mut_slice(), outputs.as_mut_slice(), keyfile.passwords.as_slice()));
+
+    //keep going so one bad password doesn't hide the pieces the others unlock
+    for (i, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(())  => { },
+            Err(e)  => {
+                try!(fs::unlink(&decpaths[i]));
+                report_error(format!("password {}: {}", i, e));
+            },
+        }
+    }
+
+    for output in outputs.iter_mut() {
+        match *output {
+            Some(ref mut output) => try!(output.flush()),
+            None                 => { },
+        }
     }
+
+    Ok(())
 }
 
-fn encrypt_interactive(plause: &mut Plause, encrypt_path: &Path, password_path: &Path, salt: &str) {
+fn migrate<R: RandomSource>(plause: &mut Plause<R>, encrypt_path: &Path, password_path: &Path) -> PlauseResult<()> {
+    let keyfile = try!(KeyFile::read(&mut BufferedReader::new(try!(File::open(password_path)))));
+
+    if keyfile.volumes != 1 {
+        report_error(String::from_str("migrating blobs split across volumes isn't supported"));
+        return Ok(());
+    }
+
+    if keyfile.version == FORMAT_VERSION {
+        println!("{} is already format version {}", password_path.display(), FORMAT_VERSION);
+        return Ok(());
+    }
+
+    //a piece that can't be read would be lost, so nothing is rewritten unless all of them are
+    match plause.migrate(&mut try!(File::open(encrypt_path)), &keyfile) {
+        Ok(())  => { },
+        Err(e)  => {
+            report_error(format!("{}, nothing was migrated", e));
+            return Ok(());
+        },
+    }
+
+    //write beside the originals and swap them in only once all of them are complete
+    let volumes      = plause.settings.volumes;
+    let encrypt_tmp  = Path::new(format!("{}.tmp", encrypt_path.display()));
+    let password_tmp = Path::new(format!("{}.tmp", password_path.display()));
+
+    try!(write_results(plause, &encrypt_tmp, &password_tmp));
+
+    for v in range(0, volumes) {
+        try!(fs::rename(&volume::volume_path(&encrypt_tmp, v, volumes), &volume::volume_path(encrypt_path, v, volumes)));
+    }
+
+    try!(fs::rename(&password_tmp, password_path));
+
+    //volumes are written beside the original blob rather than over it, the key file no longer unlocks it
+    if volumes != 1 {
+        try!(fs::unlink(encrypt_path));
+    }
+
+    println!("migrated {} pieces from format version {} to {}", keyfile.passwords.len(), keyfile.version, FORMAT_VERSION);
+    Ok(())
+}
+
+fn encrypt_duress<R: RandomSource>(plause: &mut Plause<R>, encrypt_path: &Path, password_path: &Path, duress_path: &Path, files: &Vec<Path>, decoy_dir: Option<Path>) -> PlauseResult<()> {
+    let mut passwords: Vec<Vec<u8>> = Vec::new();
+
+    for file in files.iter() {
+        let contents = try!(File::open(file).read_to_end());
+        let password = plause.random_pass(24);
+        try!(plause.add(&password, &contents));
+        passwords.push(password);
+    }
+
+    //the duress content is sized after the real items are staged so it fits what they leave free
+    let duress_contents: Vec<Vec<u8>> = match decoy_dir {
+        Some(dir) => {
+            let paths: Vec<Path> = try!(fs::readdir(&dir)).into_iter().filter(|x| x.is_file()).collect();
+
+            if paths.is_empty() {
+                report_error(String::from_str("no files found for the duress content"));
+                return Ok(());
+            }
+
+            let room = try!(plause.duress_room(paths.len(), 24));
+            let mut contents: Vec<Vec<u8>> = Vec::new();
+
+            for path in paths.iter() {
+                let content = try!(File::open(path).read_to_end());
+
+                if content.len() > room {
+                    report_error(format!("{} is {} bytes, only {} bytes of each duress file are sure to fit", path.display(), content.len(), room));
+                    return Ok(());
+                }
+
+                contents.push(content);
+            }
+
+            contents
+        },
+        None      => vec![try!(plause.duress_diary(24))],
+    };
+
+    let mut duress: Vec<Vec<u8>> = Vec::new();
+
+    for contents in duress_contents.iter() {
+        let password = plause.random_pass(24);
+        try!(plause.add(&password, contents));
+        duress.push(password);
+    }
+
+    try!(write_blob(plause, encrypt_path));
+    try!(write_keyfile(&KeyFile::new(&plause.settings, &passwords), password_path));
+    try!(write_keyfile(&KeyFile::new(&plause.settings, &duress),    duress_path));
+
+    println!("{} holds the passwords that can be given up, keep {} hidden", duress_path.display(), password_path.display());
+    Ok(())
+}
+
+fn encrypt_interactive<R: RandomSource>(plause: &mut Plause<R>, encrypt_path: &Path, password_path: &Path) -> PlauseResult<()> {
     for line in io::stdin().lines() {
-        plause.add(&random_pass(24), &line.unwrap().into_bytes().init().to_vec());
+        let password = plause.random_pass(24);
+        try!(plause.add(&password, &try!(line).into_bytes().init().to_vec()));
     }
 
-    plause.gen();
-    write_results(plause, encrypt_path, password_path, salt);
+    write_results(plause, encrypt_path, password_path)
 }
 
-fn encrypt_files(plause: &mut Plause, encrypt_path: &Path, password_path: &Path, salt: &str, files: &Vec<Path>) {
+fn encrypt_files<R: RandomSource>(plause: &mut Plause<R>, encrypt_path: &Path, password_path: &Path, files: &Vec<Path>) -> PlauseResult<()> {
     for file in files.iter() {
-        let contents = File::open(file).read_to_end();
-        match contents {
-            Ok(f)  => plause.add(&random_pass(24), &f),
-            Err(e) => fail!("error reading file: {}", e),
-        }
+        let contents = try!(File::open(file).read_to_end());
+        let password = plause.random_pass(24);
+        try!(plause.add(&password, &contents));
     }
 
-    plause.gen();
-    write_results(plause, encrypt_path, password_path, salt);
+    write_results(plause, encrypt_path, password_path)
 }
+
 fn fallback_match(c: &str, matches: &getopts::Matches, default: &str) -> String {
     if matches.opt_present(c) {
         return matches.opt_str(c).unwrap_or(default.to_string());
@@ -212,6 +570,12 @@ fn get_mode(matches: &getopts::Matches) -> Mode {
     if matches.opt_present(CONT_VERSION_MODE) {
         return Version;
     }
+
+    match matches.free.as_slice().head() {
+        Some(x) if x.as_slice() == CMD_MIGRATE => return Migrate,
+        Some(x) if x.as_slice() == CMD_DECOY   => return Decoy,
+        _                                      => { },
+    }
     
     if matches.opt_present(CONT_INTERACTIVE_MODE) {
         return Interactive;
@@ -228,21 +592,25 @@ fn get_mode(matches: &getopts::Matches) -> Mode {
     Help
 }
 
-fn write_results(plause: &mut Plause, encrypt_file: &Path, password_file: &Path, salt: &str) {
-    match File::create(encrypt_file).write(plause.content.as_slice()) {
-        Ok(f)  => f,
-        Err(e) => fail!("encrypt file error: {}", e),
-    };
+fn write_results<R: RandomSource>(plause: &mut Plause<R>, encrypt_file: &Path, password_file: &Path) -> PlauseResult<()> {
+    try!(write_blob(plause, encrypt_file));
+    write_keyfile(&plause.keyfile(), password_file)
+}
 
-    let mut pfile = match File::create(password_file) {
-        Ok(f)  => f,
-        Err(e) => fail!("password file error: {}", e),
-    };
+fn write_blob<R: RandomSource>(plause: &mut Plause<R>, encrypt_file: &Path) -> PlauseResult<()> {
+    let volumes = plause.settings.volumes;
 
-    pfile.write_line(salt.as_slice()).unwrap();
-    for i in plause.get_passwords().iter() {
-        pfile.write(i.as_slice()).unwrap();
-        pfile.write_line("").unwrap();
+    for v in range(0, volumes) {
+        let mut efile = BufferedWriter::new(try!(File::create(&volume::volume_path(encrypt_file, v, volumes))));
+        try!(plause.gen_volume_to(v, &mut efile));
+        try!(efile.flush());
     }
+
+    Ok(())
 }
 
+fn write_keyfile(keyfile: &KeyFile, password_file: &Path) -> PlauseResult<()> {
+    let mut pfile = try!(File::create(password_file));
+    try!(keyfile.write(&mut pfile));
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::collections::RingBuf;
+use std::collections::Deque;
+
+/// Matcher finds every occurrence of many needles in one pass over a blob (aho-corasick).
+/// state is carried from byte to byte so a blob can be fed in windows of any size
+pub struct Matcher {
+    states: Vec<State>,
+    /// transitions out of the root, kept dense as nearly every byte starts there
+    root:   Vec<uint>,
+}
+
+struct State {
+    next: Vec<(u8, uint)>,
+    fail: uint,
+    /// needles ending at this state, including those reached through fail links
+    out:  Vec<uint>,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            next: Vec::new(),
+            fail: 0,
+            out:  Vec::new(),
+        }
+    }
+
+    fn find(&self, byte: u8) -> Option<uint> {
+        self.next.iter().find(|&&(b, _)| b == byte).map(|&(_, s)| s)
+    }
+}
+
+impl Matcher {
+    pub fn new(needles: &[Vec<u8>]) -> Matcher {
+        let mut states: Vec<State> = vec![State::new()];
+
+        for (i, needle) in needles.iter().enumerate() {
+            let mut cur = 0u;
+
+            for &b in needle.iter() {
+                cur = match states[cur].find(b) {
+                    Some(x) => x,
+                    None    => {
+                        states.push(State::new());
+                        let id = states.len() - 1;
+                        states.get_mut(cur).next.push((b, id));
+                        id
+                    },
+                };
+            }
+
+            states.get_mut(cur).out.push(i);
+        }
+
+        //breadth first so every fail link points at an already finished state
+        let mut queue: RingBuf<uint> = RingBuf::new();
+
+        for &(_, s) in states[0].next.iter() {
+            queue.push_back(s);
+        }
+
+        loop {
+            let cur = match queue.pop_front() {
+                Some(x) => x,
+                None    => break,
+            };
+
+            let edges = states[cur].next.clone();
+
+            for &(b, child) in edges.iter() {
+                queue.push_back(child);
+
+                let mut f = states[cur].fail;
+
+                while f != 0 && states[f].find(b).is_none() {
+                    f = states[f].fail;
+                }
+
+                let fail = match states[f].find(b) {
+                    Some(x) if x != child => x,
+                    _                     => 0,
+                };
+                let inherited = states[fail].out.clone();
+
+                let state = states.get_mut(child);
+                state.fail = fail;
+                state.out.push_all(inherited.as_slice());
+            }
+        }
+
+        let root = Vec::from_fn(256, |b| states[0].find(b as u8).unwrap_or(0));
+
+        Matcher {
+            states: states,
+            root:   root,
+        }
+    }
+
+    /// the state before any byte has been seen
+    pub fn start(&self) -> uint {
+        0
+    }
+
+    pub fn step(&self, state: uint, byte: u8) -> uint {
+        let mut state = state;
+
+        loop {
+            if state == 0 {
+                return self.root[byte as uint];
+            }
+
+            match self.states[state].find(byte) {
+                Some(x) => return x,
+                None    => state = self.states[state].fail,
+            }
+        }
+    }
+
+    /// indices of the needles ending at the byte that led to state
+    pub fn matches(&self, state: uint) -> &[uint] {
+        self.states[state].out.as_slice()
+    }
+}
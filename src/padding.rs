@@ -0,0 +1,103 @@
+use std::fmt;
+use random::RandomSource;
+
+/// largest percentage random padding may add, the most a piece is ever grown by it
+pub static MAX_RANDOM_PERCENT: uint = 100;
+
+/// Padding decides how much longer than its content a piece is made, so a revealed
+/// piece doesn't disclose exactly how big the item it holds is
+#[deriving(Clone,PartialEq,Eq)]
+pub enum Padding {
+    NoPadding,
+    /// round up to the next power of two
+    PadPowerOfTwo,
+    /// round up to the smallest bucket that fits, lengths past the largest bucket are left as they are
+    PadBuckets(Vec<uint>),
+    /// add a random amount of up to this percentage of the length, at most MAX_RANDOM_PERCENT
+    PadRandom(uint),
+}
+
+impl Padding {
+    /// parse the none, pow2, buckets:LEN,LEN,.. or random:PERCENT form used by the command line
+    pub fn from_str(s: &str) -> Option<Padding> {
+        match s {
+            "none" => return Some(NoPadding),
+            "pow2" => return Some(PadPowerOfTwo),
+            _      => { },
+        }
+
+        let (kind, value) = match s.find(':') {
+            Some(x) => (s[..x], s[x + 1..]),
+            None    => return None,
+        };
+
+        match kind {
+            "buckets" => {
+                let mut buckets: Vec<uint> = Vec::new();
+
+                for x in value.split(',') {
+                    match from_str::<uint>(x) {
+                        Some(len) if len > 0 => buckets.push(len),
+                        _                    => return None,
+                    }
+                }
+
+                buckets.sort();
+                Some(PadBuckets(buckets))
+            },
+            "random" => match from_str::<uint>(value) {
+                Some(x) if x <= MAX_RANDOM_PERCENT => Some(PadRandom(x)),
+                _                                  => None,
+            },
+            _        => None,
+        }
+    }
+
+    /// the length a piece body of len bytes is padded to
+    pub fn padded_len<R:RandomSource>(&self, rng: &mut R, len: uint) -> uint {
+        match *self {
+            PadRandom(percent) => len + rng.below((len * percent / 100 + 1) as u32) as uint,
+            _                  => self.max_padded_len(len),
+        }
+    }
+
+    /// the shortest padded_len can make a piece body of len bytes
+    pub fn min_padded_len(&self, len: uint) -> uint {
+        match *self {
+            PadRandom(_) => len,
+            _            => self.max_padded_len(len),
+        }
+    }
+
+    /// the longest padded_len can make a piece body of len bytes
+    pub fn max_padded_len(&self, len: uint) -> uint {
+        match *self {
+            NoPadding => len,
+            PadPowerOfTwo => {
+                let mut padded = 1u;
+
+                while padded < len {
+                    padded *= 2;
+                }
+
+                padded
+            },
+            PadBuckets(ref buckets) => match buckets.iter().find(|&&x| x >= len) {
+                Some(&x) => x,
+                None     => len,
+            },
+            PadRandom(percent) => len + len * percent / 100,
+        }
+    }
+}
+
+impl fmt::Show for Padding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NoPadding               => write!(f, "none"),
+            PadPowerOfTwo           => write!(f, "pow2"),
+            PadBuckets(ref buckets) => write!(f, "buckets:{}", buckets.iter().map(|x| x.to_string()).collect::<Vec<String>>().connect(",")),
+            PadRandom(percent)      => write!(f, "random:{}", percent),
+        }
+    }
+}
@@ -1,55 +1,163 @@
 extern crate "rust-crypto" as crypto;
 
+use std::cmp::min;
+use std::io::{Writer, IoResult, MemWriter};
 use crypto::digest::Digest;
-use std::rand::Rng;
-use std::rand;
 use settings::Settings;
 use item::Item;
 use hash::prefix_ident;
 use hash::postfix_ident;
-use hash::hxor;
-use util::check_overlap;
+use hash::mac_len;
+use hash::ident_len;
+use keystream::Keystream;
+use hash::Hmac;
+use alloc::Allocator;
+use random::RandomSource;
+use kdf::stretch;
+use frame::{header, header_len, Fragment};
+use layout;
+use layout::{LayoutContiguous, LayoutDerived, LayoutScattered};
+use probe;
+use error::{PlauseResult, NoSpace};
 
-static SEARCH_TIMEOUT_ROUNDS: uint = 16;
+/// how much of an item is encrypted at a time while writing
+static CHUNK_SIZE: uint = 65536;
 
+/// Piece is where an item will be placed in the block, the item is only
+/// encrypted once the block is written out
 #[deriving(Clone,Show)]
 pub struct Piece {
         password:  Vec<u8>,
-    pub content:   Vec<u8>,
+    /// length of the body, the header, content and padding, before the mac
+        body_len:  uint,
+    /// where the piece falls among the fragments of its item, if the item was split
+        fragment:  Option<Fragment>,
     pub start_pos: u32,
     pub end_pos:   u32,
+    /// cells of the block the piece takes in order when scattered, empty when contiguous
+    pub cells:     Vec<u32>,
 }
 
 impl Piece {
-    pub fn new<D:Digest>(sh: &mut D, settings: &Settings, item: &Item, files: &Vec<Piece>) -> Piece {
-        let result: Vec<u8> = Vec::new()
-            + prefix_ident(sh, settings, &item.password)
-            + hxor(sh, settings, &item.password, &item.content)
-            + postfix_ident(sh, settings, &item.password);
-        
-        let result_len: u32 = result.len() as u32;
-        let start_pos:  u32 = find_insert_pos(settings, result_len, files);
+    pub fn new<D:Digest, R:RandomSource>(sh: &mut D, rng: &mut R, alloc: &mut Allocator, settings: &Settings, item: &Item) -> PlauseResult<Piece> {
+        Piece::with_key(sh, rng, alloc, settings, stretch(settings, &item.password), item.content.len(), None)
+    }
+
+    /// a piece for content_len bytes under an already stretched password
+    pub fn with_key<D:Digest, R:RandomSource>(sh: &mut D, rng: &mut R, alloc: &mut Allocator, settings: &Settings, password: Vec<u8>, content_len: uint, fragment: Option<Fragment>) -> PlauseResult<Piece> {
+        let body_len = match settings.framed {
+            true  => settings.padding.padded_len(rng, header_len(fragment.is_some()) + content_len),
+            false => content_len,
+        };
+
+        let result_len: u32 = piece_len(sh, settings, password.len(), body_len) as u32;
+        let (start_pos, cells) = match settings.layout {
+            LayoutContiguous => match alloc.alloc(rng, result_len) {
+                Some(x) => (x, Vec::new()),
+                None    => return Err(NoSpace(result_len as uint)),
+            },
+            //derived pieces start where their key says so extraction can go straight there
+            LayoutDerived    => match probe::reserve(settings, alloc, &password, result_len) {
+                Some(x) => (x, Vec::new()),
+                None    => return Err(NoSpace(result_len as uint)),
+            },
+            LayoutScattered  => match layout::reserve(settings, alloc, &password, result_len as uint) {
+                Some(x) => (0, x),
+                None    => return Err(NoSpace(result_len as uint)),
+            },
+        };
         let end_pos:    u32 = start_pos + result_len;
 
-        Piece {
-            password:  item.password.clone(),
-            content:   result,
+        Ok(Piece {
+            password:  password,
+            body_len:  body_len,
+            fragment:  fragment,
             start_pos: start_pos,
             end_pos:   end_pos,
-        }
+            cells:     cells,
+        })
     }
-}
 
-fn find_insert_pos(settings: &Settings, result_len: u32, files: &Vec<Piece>) -> u32 {
-    for _counter in range(0u, SEARCH_TIMEOUT_ROUNDS) {
-        let start_pos: u32 = rand::task_rng().gen_range(0, settings.blocksize);
+    /// encrypt content like write, spreading it over the piece's cells of block
+    pub fn scatter(&self, settings: &Settings, flags: u8, content: &[u8], block: &mut [u8]) -> PlauseResult<()> {
+        let mut stream = MemWriter::with_capacity((self.end_pos - self.start_pos) as uint);
+
+        try!(self.write(settings, flags, content, &mut stream));
+        layout::scatter(settings, &self.password, self.cells.as_slice(), stream.get_ref(), block);
+        Ok(())
+    }
+
+    /// encrypt content as prefix ident, body, mac and postfix ident straight to writer,
+    /// flags recording in the header how content was transformed
+    pub fn write<W:Writer>(&self, settings: &Settings, flags: u8, content: &[u8], writer: &mut W) -> PlauseResult<()> {
+        let keystream = Keystream::new(settings, &self.password);
+
+        try!(writer.write(prefix_ident(&keystream, settings, &self.password).as_slice()));
+
+        let mut mh     = settings.digest.hasher();
+        let mut hmac   = if settings.authenticate { Some(Hmac::new(&mut mh, settings, &self.password)) } else { None };
+        let mut stream = keystream.clone();
+
+        let mut buf: Vec<u8> = Vec::with_capacity(min(self.body_len, CHUNK_SIZE));
 
-        match check_overlap(settings, start_pos, result_len, files) {
-            true  => { },
-            false => { return start_pos; },
+        if settings.framed {
+            try!(seal(&mut stream, &mut hmac, &mut buf, header(flags, content.len() as u64, &self.fragment).as_slice(), writer));
         }
+
+        for chunk in content.chunks(CHUNK_SIZE) {
+            try!(seal(&mut stream, &mut hmac, &mut buf, chunk, writer));
+        }
+
+        //padding is zeros under the keystream, indistinguishable from the content before it
+        let mut padding = self.body_len - content.len() - if settings.framed { header_len(self.fragment.is_some()) } else { 0 };
+        let zeros: Vec<u8> = Vec::from_elem(min(padding, CHUNK_SIZE), 0u8);
+
+        while padding > 0 {
+            let len = min(padding, CHUNK_SIZE);
+            try!(seal(&mut stream, &mut hmac, &mut buf, zeros[..len], writer));
+            padding -= len;
+        }
+
+        match hmac {
+            Some(h) => {
+                let mut tag = h.result();
+                stream.apply(tag.as_mut_slice());
+                try!(writer.write(tag.as_slice()));
+            },
+            None    => { },
+        }
+
+        try!(writer.write(postfix_ident(&keystream, settings, &self.password).as_slice()));
+        Ok(())
     }
+}
 
-    fail!("couldn't find non overlapping segment, try increasing blocksize");
+/// mac plain, then write it to writer under the keystream using buf
+fn seal<'a, D:Digest, W:Writer>(stream: &mut Keystream, hmac: &mut Option<Hmac<'a, D>>, buf: &mut Vec<u8>, plain: &[u8], writer: &mut W) -> IoResult<()> {
+    match *hmac {
+        Some(ref mut h) => h.input(plain),
+        None            => { },
+    }
+
+    buf.clear();
+    buf.push_all(plain);
+    stream.apply(buf.as_mut_slice());
+    writer.write(buf.as_slice())
+}
+
+/// the most a piece for content_len bytes under a key_len byte stretched password can take, padding at its longest
+pub fn max_piece_len<D:Digest>(sh: &D, settings: &Settings, key_len: uint, content_len: uint, fragment: bool) -> uint {
+    let body_len = match settings.framed {
+        true  => settings.padding.max_padded_len(header_len(fragment) + content_len),
+        false => content_len,
+    };
+
+    piece_len(sh, settings, key_len, body_len)
 }
 
+/// length of the piece holding a body_len byte body between its two idents
+fn piece_len<D:Digest>(sh: &D, settings: &Settings, key_len: uint, body_len: uint) -> uint {
+    let tag_len = if settings.authenticate { mac_len(sh) } else { 0 };
+
+    ident_len(settings, key_len) + body_len + tag_len + ident_len(settings, key_len)
+}
@@ -1,57 +1,250 @@
-use crypto::sha2::Sha256;
+use std::cmp::{max, min};
+use std::io::Reader;
+use std::io::Writer;
+use std::io::{Seek, SeekSet, SeekEnd};
+use std::io::MemWriter;
+use std::rand::OsRng;
+use std::rand::IsaacRng;
 use settings::Settings;
-use block::Block;
+use writer::BlobWriter;
+use reader::BlobReader;
+use reader::extract_stream;
+use reader::{Location, locate_all, extract_located, extract_all_located};
 use item::Item;
+use keyfile::{KeyFile, valid_password};
+use error::{PlauseResult, OversizeItem, DuplicatePassword, InvalidPassword};
+use random::{RandomSource, os_random, seeded_random};
+use util;
+use duress;
+use volume;
 
 
 /// Plause contains the core functionality for interacting with plause
-#[deriving(Clone,Show)]
-pub struct Plause {
+pub struct Plause<R> {
     pub settings: Settings,
         items:    Vec<Item>,
     pub content:  Vec<u8>,
+        rng:      R,
 }
 
-impl Plause {
-    pub fn new(blocksize: u32, salt: &str) -> Plause {
+impl Plause<OsRng> {
+    /// a plause drawing its randomness from the operating system csprng
+    pub fn new(blocksize: u32, salt: &str) -> PlauseResult<Plause<OsRng>> {
+        Ok(Plause::with_rng(blocksize, salt, try!(os_random())))
+    }
+}
+
+impl Plause<IsaacRng> {
+    /// a deterministic plause, the same seed and inputs always produce a byte identical blob,
+    /// only meant for debugging and tests as anyone knowing the seed can recreate the layout
+    pub fn with_seed(blocksize: u32, salt: &str, seed: u64) -> Plause<IsaacRng> {
+        Plause::with_rng(blocksize, salt, seeded_random(seed))
+    }
+}
+
+impl<R: RandomSource> Plause<R> {
+    /// a plause drawing filler, placement and passwords from rng
+    pub fn with_rng(blocksize: u32, salt: &str, rng: R) -> Plause<R> {
         Plause {
             settings: Settings::new(blocksize, salt),
             items:    Vec::new(),
             content:  Vec::new(),
+            rng:      rng,
         }
     }
 
     /// read data in for later extraction tasks
-    pub fn import(&mut self, content: Vec<u8>) {
-        self.content = content;
+    pub fn import<R: Reader>(&mut self, reader: &mut R) -> PlauseResult<()> {
+        self.content = try!(reader.read_to_end());
+        Ok(())
     }
 
     pub fn set_salt(&mut self, salt: Vec<u8>) {
-        self.settings.salt = salt;
+        self.settings.set_salt(salt);
+    }
+
+    /// use the salt and settings recorded in a key file
+    pub fn set_keyfile(&mut self, keyfile: &KeyFile) {
+        keyfile.apply(&mut self.settings);
+    }
+
+    /// find the piece unlocked by password in the imported content and decrypt it
+    pub fn extract(&self, password: &Vec<u8>) -> PlauseResult<Vec<u8>> {
+        let mut sh = self.settings.digest.hasher();
+
+        BlobReader::new(self.content.as_slice()).extract(&mut sh, &self.settings, password)
+    }
+
+    /// find the piece unlocked by password while streaming the blob from reader, writing it decrypted to writer.
+    /// memory use is bounded by a window regardless of the blob's size
+    pub fn extract_to<B: Reader, W: Writer>(&self, reader: &mut B, writer: &mut W, password: &Vec<u8>) -> PlauseResult<()> {
+        extract_stream(reader, writer, &self.settings, password)
+    }
+
+    /// find and decrypt the piece of every password in the imported content with one scan,
+    /// results are in the same order as passwords
+    pub fn extract_all(&self, passwords: &[Vec<u8>]) -> Vec<PlauseResult<Vec<u8>>> {
+        let mut sh = self.settings.digest.hasher();
+
+        BlobReader::new(self.content.as_slice()).extract_all(&mut sh, &self.settings, passwords)
+    }
+
+    /// find where the piece of every password begins with one pass over the blob streamed from reader,
+    /// hand each location to extract_located
+    pub fn locate_all<B: Reader + Seek>(&self, reader: &mut B, passwords: &[Vec<u8>]) -> PlauseResult<Vec<Location>> {
+        locate_all(reader, &self.settings, passwords)
+    }
+
+    /// decrypt the piece at a location found by locate_all to writer, seeking straight to it
+    pub fn extract_located<B: Reader + Seek, W: Writer>(&self, reader: &mut B, writer: &mut W, location: &Location) -> PlauseResult<()> {
+        extract_located(reader, writer, &self.settings, location)
+    }
+
+    /// decrypt the piece at every location found by locate_all to the writer at the same index, skipping those
+    /// without one, with a single more pass over reader for the rest of every split item. results are in the
+    /// same order as locations, an error reading the blob fails them all
+    pub fn extract_all_located<B: Reader + Seek, W: Writer>(&self, reader: &mut B, writers: &mut [Option<W>], locations: &[Location]) -> PlauseResult<Vec<PlauseResult<()>>> {
+        extract_all_located(reader, writers, &self.settings, locations)
+    }
+
+    /// decrypt the item of every password from its volumes, one reader per volume in order, each item's
+    /// shares appended to the writer at the same index. an item failing in a volume has its writer taken
+    /// out of writers so the volumes after it pass it by, results are in the same order as passwords
+    pub fn extract_volumes<B: Reader + Seek, W: Writer>(&self, volumes: &mut [B], writers: &mut [Option<W>], passwords: &[Vec<u8>]) -> PlauseResult<Vec<PlauseResult<()>>> {
+        let count = volumes.len();
+        let mut results: Vec<PlauseResult<()>> = range(0, passwords.len()).map(|_| Ok(())).collect();
+
+        for (v, volume) in volumes.iter_mut().enumerate() {
+            let shares: Vec<Vec<u8>> = passwords.iter().map(|x| volume::volume_password(x, v, count)).collect();
+
+            try!(volume.seek(0, SeekSet));
+            let locations = try!(self.locate_all(volume, shares.as_slice()));
+
+            for (i, result) in try!(self.extract_all_located(volume, writers, locations.as_slice())).into_iter().enumerate() {
+                if result.is_err() {
+                    writers[i] = None;
+                    *results.get_mut(i) = result;
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// stage every piece of the blob in reader, read under the settings of keyfile, to be embedded
+    /// again in this plause's settings on the next gen. the blob's size and the key file's salt are kept,
+    /// nothing is staged unless every piece could be read
+    pub fn migrate<B: Reader + Seek>(&mut self, reader: &mut B, keyfile: &KeyFile) -> PlauseResult<()> {
+        //this plause's settings describe the new blob, the key file the old one
+        let settings = self.settings.clone();
+        self.set_keyfile(keyfile);
+
+        let contents = self.extract_every(reader, keyfile.passwords.as_slice());
+        self.settings = settings;
+        let contents = try!(contents);
+
+        try!(reader.seek(0, SeekEnd));
+        self.settings.blocksize = try!(reader.tell()) as u32;
+        self.set_salt(keyfile.salt.clone());
+
+        for (password, content) in keyfile.passwords.iter().zip(contents.iter()) {
+            try!(self.add(password, content));
+        }
+
+        Ok(())
     }
 
-    pub fn extract(&mut self, password: &Vec<u8>) -> Vec<u8> {
-        let mut sh = box Sha256::new();
-        let mut fb = Block::new(&mut *sh, &self.settings, &self.items);
-        fb.content = self.content.clone();
-        fb.extract(&mut *sh, &self.settings, password)
+    /// the content of every password's piece in the blob from reader, in order
+    fn extract_every<B: Reader + Seek>(&self, reader: &mut B, passwords: &[Vec<u8>]) -> PlauseResult<Vec<Vec<u8>>> {
+        try!(reader.seek(0, SeekSet));
+
+        let locations = try!(self.locate_all(reader, passwords));
+        let mut contents: Vec<Option<MemWriter>> = Vec::from_fn(locations.len(), |_| Some(MemWriter::new()));
+
+        for result in try!(self.extract_all_located(reader, contents.as_mut_slice(), locations.as_slice())).into_iter() {
+            try!(result);
+        }
+
+        Ok(contents.into_iter().map(|x| x.unwrap().unwrap()).collect())
     }
 
-    pub fn add(&mut self, password: &Vec<u8>, content: &Vec<u8>) {
-        if content.len() as u32 > self.settings.blocksize {
-            fail!("size of file({}) is bigger than blocksize({})", content.len(), self.settings.blocksize);
+    /// stage content to be embedded under password on the next gen, compressed first if settings ask for it
+    pub fn add(&mut self, password: &Vec<u8>, content: &Vec<u8>) -> PlauseResult<()> {
+        if !valid_password(password) {
+            return Err(InvalidPassword);
         }
+
         for i in self.items.iter() {
-            assert!(password.clone() != i.password, "using the same password for multiple inputs allows an agent to see where datablocks begin/end.. don't do it.");
+            if password == &i.password {
+                return Err(DuplicatePassword);
+            }
+        }
+
+        //compression is recorded in the piece header, so unframed formats can't use it
+        let item = match self.settings.framed {
+            true  => Item::compressed(password, content, self.settings.compression.clone()),
+            false => Item::new(password, content),
+        };
+
+        //with several volumes only each one's share has to fit in a block
+        if volume::chunk_len(item.content.len(), self.settings.volumes) as u32 > self.settings.blocksize {
+            return Err(OversizeItem(item.content.len(), self.settings.blocksize));
         }
 
-        self.items.push(Item::new(password, content));
+        self.items.push(item);
+        Ok(())
     }
 
-    pub fn gen(&mut self) {
-        let mut sh = box Sha256::new();
-        
-        self.content = Block::new(&mut *sh, &self.settings, &self.items).content;
+    /// generate a blob containing every staged item into content
+    pub fn gen(&mut self) -> PlauseResult<()> {
+        let mut writer = MemWriter::with_capacity(self.settings.blocksize as uint);
+
+        try!(self.gen_to(&mut writer));
+        self.content = writer.unwrap();
+        Ok(())
+    }
+
+    /// generate a blob containing every staged item straight to writer,
+    /// memory use is bounded by the largest item rather than the blocksize
+    pub fn gen_to<W: Writer>(&mut self, writer: &mut W) -> PlauseResult<()> {
+        let mut sh = self.settings.digest.hasher();
+        let blob   = try!(BlobWriter::new(&mut sh, &mut self.rng, &self.settings, &self.items));
+
+        blob.write_to(&mut self.rng, &self.settings, writer)
+    }
+
+    /// generate volume index of settings.volumes straight to writer, every staged item
+    /// contributing its share under a password derived for that volume
+    pub fn gen_volume_to<W: Writer>(&mut self, index: uint, writer: &mut W) -> PlauseResult<()> {
+        if self.settings.volumes == 1 {
+            return self.gen_to(writer);
+        }
+
+        let chunks = try!(volume::chunk_items(&self.items, index, self.settings.volumes));
+        let mut sh = self.settings.digest.hasher();
+        let blob   = try!(BlobWriter::new(&mut sh, &mut self.rng, &self.settings, &chunks));
+
+        blob.write_to(&mut self.rng, &self.settings, writer)
+    }
+
+    /// an alphanumeric password of len characters from this plause's random source
+    pub fn random_pass(&mut self, len: uint) -> Vec<u8> {
+        util::random_pass(&mut self.rng, len)
+    }
+
+    /// the most content each of count more items under password_len byte passwords can hold
+    /// and still be sure to fit beside the staged items
+    pub fn duress_room(&self, count: uint, password_len: uint) -> PlauseResult<uint> {
+        duress::room(&self.settings, &self.items, count, password_len)
+    }
+
+    /// a generated diary sized plausibly for the room the staged items leave, to be added
+    /// under a password_len byte password that can be given up under duress
+    pub fn duress_diary(&mut self, password_len: uint) -> PlauseResult<Vec<u8>> {
+        let room = try!(self.duress_room(1, password_len));
+        let len  = max(duress::plausible_len(&mut self.rng, room), min(room, duress::shortest_entry()));
+
+        duress::diary(&mut self.rng, len)
     }
 
     pub fn get_passwords(&self) -> Vec<Vec<u8>> {
@@ -63,4 +256,9 @@ impl Plause {
 
         return result;
     }
+
+    /// the key file needed to extract every staged item
+    pub fn keyfile(&self) -> KeyFile {
+        KeyFile::new(&self.settings, &self.get_passwords())
+    }
 }
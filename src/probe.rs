@@ -0,0 +1,106 @@
+use std::cmp::min;
+use std::io::{Reader, Seek, SeekSet, IoResult, EndOfFile};
+use settings::Settings;
+use alloc::Allocator;
+use layout::Permutation;
+
+static PROBE_SALT: &'static [u8] = b"pr0b3$t@rt5";
+
+/// probes read one at a time from a seekable blob before it is searched for the prefix instead,
+/// a placed piece is nearly always found well within these
+static PROBE_READS: u32 = 64;
+
+/// how much of a blob is searched at a time once the probes run out
+static WINDOW_SIZE: uint = 65536;
+
+/// every start of a size byte block in the order key's piece tries them. it reaches each
+/// start exactly once, so a piece fits whenever any gap can hold it
+fn sequence(settings: &Settings, key: &Vec<u8>, size: u32) -> Permutation {
+    Permutation::new(settings, PROBE_SALT, key.as_slice(), size)
+}
+
+/// take the first start along key's sequence where len bytes are free
+pub fn reserve(settings: &Settings, alloc: &mut Allocator, key: &Vec<u8>, len: u32) -> Option<u32> {
+    if len == 0 || alloc.largest_gap() < len {
+        return None;
+    }
+
+    let sequence = sequence(settings, key, settings.blocksize);
+
+    for i in range(0, sequence.size()) {
+        let start = sequence.apply(i);
+
+        if start as u64 + len as u64 <= settings.blocksize as u64 && alloc.reserve(start, len) {
+            return Some(start);
+        }
+    }
+
+    None
+}
+
+/// where the body of key's piece begins in content, just past the first probed start holding prefix
+pub fn find(settings: &Settings, key: &Vec<u8>, content: &[u8], prefix: &[u8]) -> Option<uint> {
+    let sequence = sequence(settings, key, content.len() as u32);
+
+    for i in range(0, min(sequence.size(), PROBE_READS)) {
+        let start = sequence.apply(i) as uint;
+        let end   = start + prefix.len();
+
+        if end <= content.len() && content[start..end] == prefix {
+            return Some(end);
+        }
+    }
+
+    //past the probes only the piece's own prefix ident can be at any start, so it is searched for
+    position(content, prefix).map(|x| x + prefix.len())
+}
+
+/// where the body of key's piece begins in a seekable blob of size bytes. the first probes are read
+/// one at a time, a blob that holds no piece for key is searched after them a window at a time
+pub fn find_in<R:Reader + Seek>(reader: &mut R, settings: &Settings, key: &Vec<u8>, size: u64, prefix: &[u8]) -> IoResult<Option<u64>> {
+    let sequence = sequence(settings, key, size as u32);
+
+    for i in range(0, min(sequence.size(), PROBE_READS)) {
+        let start = sequence.apply(i) as u64;
+        let end   = start + prefix.len() as u64;
+
+        if end > size {
+            continue;
+        }
+
+        try!(reader.seek(start as i64, SeekSet));
+
+        if try!(reader.read_exact(prefix.len())).as_slice() == prefix {
+            return Ok(Some(end));
+        }
+    }
+
+    try!(reader.seek(0, SeekSet));
+
+    let mut buf: Vec<u8> = Vec::with_capacity(WINDOW_SIZE);
+    let mut offset = 0u64;
+
+    loop {
+        let more = match reader.push(WINDOW_SIZE, &mut buf) {
+            Ok(_)                             => true,
+            Err(ref e) if e.kind == EndOfFile => false,
+            Err(e)                            => return Err(e),
+        };
+
+        match position(buf.as_slice(), prefix) {
+            Some(x)       => return Ok(Some(offset + (x + prefix.len()) as u64)),
+            None if !more => return Ok(None),
+            None          => {
+                //keep enough of the tail that a prefix straddling the window edge is still found
+                let drop = buf.len() - min(buf.len(), prefix.len() - 1);
+                buf     = buf[drop..].to_vec();
+                offset += drop as u64;
+            },
+        }
+    }
+}
+
+/// position of the first occurrence of needle in haystack
+fn position(haystack: &[u8], needle: &[u8]) -> Option<uint> {
+    haystack.windows(needle.len()).position(|x| x == needle)
+}
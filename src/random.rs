@@ -0,0 +1,37 @@
+use std::rand::Rng;
+use std::rand::OsRng;
+use std::rand::IsaacRng;
+use std::rand::SeedableRng;
+use error::PlauseResult;
+
+/// RandomSource supplies every random decision plause makes: filler bytes,
+/// piece placement and generated passwords
+pub trait RandomSource {
+    /// fill buf with bytes uniform over every value 0x00..0xFF
+    fn fill(&mut self, buf: &mut [u8]);
+
+    /// a uniform value in 0..n, n must be non zero
+    fn below(&mut self, n: u32) -> u32;
+}
+
+impl<R: Rng> RandomSource for R {
+    fn fill(&mut self, buf: &mut [u8]) {
+        self.fill_bytes(buf);
+    }
+
+    fn below(&mut self, n: u32) -> u32 {
+        //gen_range rejects the biased zone so every value is equally likely
+        self.gen_range(0, n)
+    }
+}
+
+/// the default source, backed by the operating system csprng
+pub fn os_random() -> PlauseResult<OsRng> {
+    Ok(try!(OsRng::new()))
+}
+
+/// a reproducible source, the same seed and inputs always produce a byte identical blob
+pub fn seeded_random(seed: u64) -> IsaacRng {
+    let words = [seed as u32, (seed >> 32) as u32];
+    SeedableRng::from_seed(words.as_slice())
+}
@@ -0,0 +1,650 @@
+extern crate "rust-crypto" as crypto;
+
+use std::cmp::min;
+use std::io::{Reader, Writer, Seek, SeekSet, SeekEnd, IoResult, EndOfFile, MemReader, BufReader};
+use crypto::digest::Digest;
+use settings::Settings;
+use hash::prefix_ident;
+use hash::postfix_ident;
+use hash::hxor;
+use keystream::Keystream;
+use hash::Hmac;
+use hash::mac;
+use hash::mac_len;
+use util::fixed_time_eq;
+use matcher::Matcher;
+use error::{PlauseResult, NotFound, Unterminated, IntegrityError};
+use kdf::stretch;
+use frame::{FrameWriter, Frame, Fragment, unframe, framed_len, header_len};
+use fragment::fragment_password;
+use layout::{LayoutDerived, LayoutScattered, gather};
+use probe;
+
+/// how much of a streamed blob is read at a time
+static WINDOW_SIZE: uint = 65536;
+
+/// BlobReader searches an existing blob for pieces without modifying it
+pub struct BlobReader<'a> {
+    content: &'a [u8],
+}
+
+impl<'a> BlobReader<'a> {
+    pub fn new(content: &'a [u8]) -> BlobReader<'a> {
+        BlobReader {
+            content: content,
+        }
+    }
+
+    /// find the piece unlocked by password and decrypt it
+    pub fn extract<D:Digest>(&self, sh: &mut D, settings: &Settings, password: &Vec<u8>) -> PlauseResult<Vec<u8>> {
+        self.extract_all(sh, settings, &[password.clone()]).pop().unwrap()
+    }
+
+    /// find and decrypt the piece of every password with a single scan of the blob,
+    /// the remaining fragments of any item that was split take one more
+    pub fn extract_all<D:Digest>(&self, sh: &mut D, settings: &Settings, passwords: &[Vec<u8>]) -> Vec<PlauseResult<Vec<u8>>> {
+        let frames = self.frames(sh, settings, passwords);
+        let mut rest: Vec<Vec<u8>> = Vec::new();
+
+        for (password, frame) in passwords.iter().zip(frames.iter()) {
+            match *frame {
+                Ok(Frame { fragment: Some(ref x), .. }) if x.seq == 0 => rest.extend(range(1, x.total).map(|seq| fragment_password(password, seq))),
+                _                                                      => { },
+            }
+        }
+
+        let mut rest = match rest.is_empty() {
+            true  => Vec::new(),
+            false => self.frames(sh, settings, rest.as_slice()),
+        }.into_iter();
+
+        frames.into_iter().map(|frame| {
+            let frame = try!(frame);
+
+            match frame.fragment {
+                None    => Ok(frame.content),
+                Some(x) => match x.seq {
+                    0 => reassemble(frame.content, x.total, rest.by_ref().take(x.total - 1).collect()),
+                    _ => Err(IntegrityError),
+                },
+            }
+        }).collect()
+    }
+
+    /// find and decrypt the piece of every password with a single scan of the blob,
+    /// or by following their probe sequences where the layout derives their starts
+    fn frames<D:Digest>(&self, sh: &mut D, settings: &Settings, passwords: &[Vec<u8>]) -> Vec<PlauseResult<Frame>> {
+        let keys: Vec<Vec<u8>> = passwords.iter().map(|x| stretch(settings, x)).collect();
+        let streams: Vec<Keystream> = keys.iter().map(|x| Keystream::new(settings, x)).collect();
+
+        //a scattered piece is only contiguous once its cells are gathered along its sequence
+        if settings.layout == LayoutScattered {
+            let mut blob = BufReader::new(self.content);
+            let mut result: Vec<PlauseResult<Frame>> = Vec::with_capacity(keys.len());
+
+            for (key, keystream) in keys.iter().zip(streams.iter()) {
+                result.push(open_gathered(sh, settings, key, keystream, &mut blob, self.content.len() as u64));
+            }
+
+            return result;
+        }
+
+        if settings.layout == LayoutDerived {
+            let mut result: Vec<PlauseResult<Frame>> = Vec::with_capacity(keys.len());
+
+            for (key, keystream) in keys.iter().zip(streams.iter()) {
+                let begin = probe::find(settings, key, self.content, prefix_ident(keystream, settings, key).as_slice());
+
+                result.push(open_from(sh, settings, key, keystream, self.content, begin));
+            }
+
+            return result;
+        }
+
+        let needles = needles(settings, keys.as_slice(), streams.as_slice());
+        let matcher = Matcher::new(needles.as_slice());
+
+        let mut begins: Vec<Option<uint>> = Vec::from_elem(keys.len(), None);
+        let mut ends:   Vec<Vec<uint>>    = Vec::from_fn(keys.len(), |_| Vec::new());
+        let mut state = matcher.start();
+
+        for (i, &b) in self.content.iter().enumerate() {
+            state = matcher.step(state, b);
+
+            for &m in matcher.matches(state).iter() {
+                let key   = m / 2;
+                let start = i + 1 - needles[m].len();
+
+                if m % 2 == 0 {
+                    if begins[key].is_none() {
+                        *begins.get_mut(key) = Some(i + 1);
+                    }
+                } else {
+                    match begins[key] {
+                        Some(begin) if start >= begin => ends.get_mut(key).push(start),
+                        _                             => { },
+                    }
+                }
+            }
+        }
+
+        range(0, keys.len()).map(|i| match begins[i] {
+            Some(begin) => open(sh, settings, &keys[i], &streams[i], self.content, begin, ends[i].as_slice()),
+            None        => Err(NotFound),
+        }).collect()
+    }
+}
+
+/// decrypt the body starting at begin, trying each postfix position in turn as
+/// a stray postfix match inside the piece fails verification
+fn open<D:Digest>(sh: &mut D, settings: &Settings, password: &Vec<u8>, keystream: &Keystream, content: &[u8], begin: uint, ends: &[uint]) -> PlauseResult<Frame> {
+    if ends.is_empty() {
+        return Err(Unterminated);
+    }
+
+    for &end in ends.iter() {
+        let msg = hxor(keystream, content[begin..end]);
+
+        if !settings.authenticate {
+            return unframed(settings, msg);
+        }
+
+        match verify(sh, settings, password, msg) {
+            Some(x) => return unframed(settings, x),
+            None    => { },
+        }
+    }
+
+    Err(IntegrityError)
+}
+
+/// decrypt the body starting at begin, found without a scan. a framed body's header says how
+/// long it is, so only the postfix positions its padding allows are tried
+fn open_from<D:Digest>(sh: &mut D, settings: &Settings, password: &Vec<u8>, keystream: &Keystream, content: &[u8], begin: Option<uint>) -> PlauseResult<Frame> {
+    let begin = match begin {
+        Some(x) => x,
+        None    => return Err(NotFound),
+    };
+
+    let postfix = postfix_ident(keystream, settings, password);
+
+    if !settings.framed {
+        let ends = find_all(content, postfix.as_slice(), begin);
+        return open(sh, settings, password, keystream, content, begin, ends.as_slice());
+    }
+
+    let start  = hxor(keystream, content[begin..min(content.len(), begin + header_len(true))]);
+    let framed = match framed_len(start.as_slice()) {
+        Some(x) if x <= (content.len() - begin) as u64 => x as uint,
+        _                                              => return Err(IntegrityError),
+    };
+
+    //the postfix follows the padded body and its mac, anywhere in between when the padding is random
+    let tag_len = if settings.authenticate { mac_len(&*sh) } else { 0 };
+    let from    = begin + settings.padding.min_padded_len(framed) + tag_len;
+    let to      = min(content.len(), begin + settings.padding.max_padded_len(framed) + tag_len + postfix.len());
+
+    let ends = match from < to {
+        true  => find_all(content[..to], postfix.as_slice(), from),
+        false => Vec::new(),
+    };
+
+    open(sh, settings, password, keystream, content, begin, ends.as_slice())
+}
+
+/// strip the header and padding from a decrypted body if the format has them
+fn unframed(settings: &Settings, body: Vec<u8>) -> PlauseResult<Frame> {
+    match settings.framed {
+        true  => unframe(body.as_slice()),
+        false => Ok(Frame { content: body, fragment: None }),
+    }
+}
+
+/// the content of an item from its first fragment and the rest in order,
+/// each must carry the sequence number of the password that found it
+fn reassemble(first: Vec<u8>, total: uint, rest: Vec<PlauseResult<Frame>>) -> PlauseResult<Vec<u8>> {
+    let mut content = first;
+
+    for (i, frame) in rest.into_iter().enumerate() {
+        let frame = try!(frame);
+
+        match frame.fragment {
+            Some(ref x) if x.seq == i + 1 && x.total == total => content.push_all(frame.content.as_slice()),
+            _                                                 => return Err(IntegrityError),
+        }
+    }
+
+    Ok(content)
+}
+
+/// split the mac off the end of a decrypted body, returning the content if it verifies
+fn verify<D:Digest>(sh: &mut D, settings: &Settings, password: &Vec<u8>, msg: Vec<u8>) -> Option<Vec<u8>> {
+    let tag_len = mac_len(sh);
+
+    if msg.len() < tag_len {
+        return None;
+    }
+
+    let split = msg.len() - tag_len;
+    let tag   = mac(sh, settings, password, msg[..split]);
+
+    match fixed_time_eq(tag.as_slice(), msg[split..]) {
+        true  => Some(msg[..split].to_vec()),
+        false => None,
+    }
+}
+
+/// gather key's scattered piece from a seekable blob of size bytes and decrypt it
+fn open_gathered<D:Digest, R:Reader + Seek>(sh: &mut D, settings: &Settings, password: &Vec<u8>, keystream: &Keystream, reader: &mut R, size: u64) -> PlauseResult<Frame> {
+    let gathered = try!(gather_piece(reader, settings, password, keystream, size));
+    let begin    = gathered_begin(settings, password, keystream, gathered.as_slice());
+
+    open_from(sh, settings, password, keystream, gathered.as_slice(), begin)
+}
+
+/// the cells of key's scattered piece in a seekable blob of size bytes, read up to its postfix ident
+fn gather_piece<R:Reader + Seek>(reader: &mut R, settings: &Settings, key: &Vec<u8>, keystream: &Keystream, size: u64) -> IoResult<Vec<u8>> {
+    let prefix  = prefix_ident(keystream, settings, key);
+    let postfix = postfix_ident(keystream, settings, key);
+
+    gather(reader, settings, key, size, prefix.as_slice(), postfix.as_slice())
+}
+
+/// where the body begins in a gathered piece, a gathered piece starts with its prefix ident
+fn gathered_begin(settings: &Settings, key: &Vec<u8>, keystream: &Keystream, gathered: &[u8]) -> Option<uint> {
+    let prefix = prefix_ident(keystream, settings, key);
+
+    match gathered.starts_with(prefix.as_slice()) {
+        true  => Some(prefix.len()),
+        false => None,
+    }
+}
+
+/// prefix ident of every key at even indices, followed by its postfix ident
+fn needles(settings: &Settings, keys: &[Vec<u8>], streams: &[Keystream]) -> Vec<Vec<u8>> {
+    let mut result: Vec<Vec<u8>> = Vec::with_capacity(keys.len() * 2);
+
+    for (key, keystream) in keys.iter().zip(streams.iter()) {
+        result.push(prefix_ident(keystream, settings, key));
+        result.push(postfix_ident(keystream, settings, key));
+    }
+
+    result
+}
+
+/// Location is where a password's piece body begins within a streamed blob
+pub struct Location {
+    /// the password as given, fragments of the item derive theirs from it
+        password:  Vec<u8>,
+        key:       Vec<u8>,
+        keystream: Keystream,
+    pub begin:     Option<u64>,
+    /// the cells of a scattered piece gathered in order, begin is an offset into them
+        gathered:  Option<Vec<u8>>,
+}
+
+/// find where the piece of every password begins with a single pass over reader,
+/// only a window of the blob is held in memory at once. with derived starts the probed offsets
+/// are read first and the blob is only searched a window at a time when none of them holds the
+/// piece, a scattered piece is gathered cell by cell along its sequence
+pub fn locate_all<R:Reader + Seek>(reader: &mut R, settings: &Settings, passwords: &[Vec<u8>]) -> PlauseResult<Vec<Location>> {
+    let keys: Vec<Vec<u8>> = passwords.iter().map(|x| stretch(settings, x)).collect();
+    let streams: Vec<Keystream> = keys.iter().map(|x| Keystream::new(settings, x)).collect();
+
+    if settings.layout == LayoutScattered {
+        try!(reader.seek(0, SeekEnd));
+        let size = try!(reader.tell());
+        let mut result: Vec<Location> = Vec::with_capacity(keys.len());
+
+        for i in range(0, keys.len()) {
+            let gathered = try!(gather_piece(reader, settings, &keys[i], &streams[i], size));
+
+            result.push(Location {
+                password:  passwords[i].clone(),
+                key:       keys[i].clone(),
+                keystream: streams[i].clone(),
+                begin:     gathered_begin(settings, &keys[i], &streams[i], gathered.as_slice()).map(|x| x as u64),
+                gathered:  Some(gathered),
+            });
+        }
+
+        return Ok(result);
+    }
+
+    let needles = needles(settings, keys.as_slice(), streams.as_slice());
+    let mut begins: Vec<Option<u64>> = Vec::from_elem(keys.len(), None);
+
+    match settings.layout {
+        LayoutDerived => {
+            try!(reader.seek(0, SeekEnd));
+            let size = try!(reader.tell());
+
+            for i in range(0, keys.len()) {
+                *begins.get_mut(i) = try!(probe::find_in(reader, settings, &keys[i], size, needles[i * 2].as_slice()));
+            }
+        },
+        _             => try!(scan_begins(reader, needles.as_slice(), begins.as_mut_slice())),
+    }
+
+    Ok(range(0, keys.len()).map(|i| Location {
+        password:  passwords[i].clone(),
+        key:       keys[i].clone(),
+        keystream: streams[i].clone(),
+        begin:     begins[i],
+        gathered:  None,
+    }).collect())
+}
+
+/// record where the body after each prefix needle first begins in begins with one pass over reader
+fn scan_begins<R:Reader>(reader: &mut R, needles: &[Vec<u8>], begins: &mut [Option<u64>]) -> IoResult<()> {
+    let matcher = Matcher::new(needles);
+
+    let mut buf: Vec<u8> = Vec::with_capacity(WINDOW_SIZE);
+    let mut offset = 0u64;
+    let mut state  = matcher.start();
+
+    loop {
+        buf.clear();
+
+        if !try!(read_window(reader, &mut buf)) && buf.is_empty() {
+            return Ok(());
+        }
+
+        for &b in buf.iter() {
+            state   = matcher.step(state, b);
+            offset += 1;
+
+            for &m in matcher.matches(state).iter() {
+                if m % 2 == 0 && begins[m / 2].is_none() {
+                    begins[m / 2] = Some(offset);
+                }
+            }
+        }
+    }
+}
+
+/// decrypt a piece found by locate_all to writer, only a window of the blob is held in memory at once.
+/// the rest of a fragmented item is located with another pass over reader and follows in order,
+/// extract_all_located shares that pass between every item.
+/// content reaches writer before the mac is checked, discard it if IntegrityError is returned
+pub fn extract_located<R:Reader + Seek, W:Writer>(reader: &mut R, writer: &mut W, settings: &Settings, location: &Location) -> PlauseResult<()> {
+    let total = match try!(stream_located(reader, writer, settings, location)) {
+        None                     => return Ok(()),
+        Some(ref x) if x.seq == 0 => x.total,
+        Some(_)                  => return Err(IntegrityError),
+    };
+
+    let passwords: Vec<Vec<u8>> = range(1, total).map(|seq| fragment_password(&location.password, seq)).collect();
+
+    try!(reader.seek(0, SeekSet));
+    let locations = try!(locate_all(reader, settings, passwords.as_slice()));
+
+    for (i, rest) in locations.iter().enumerate() {
+        match try!(stream_located(reader, writer, settings, rest)) {
+            Some(ref x) if x.seq == i + 1 && x.total == total => { },
+            _                                                 => return Err(IntegrityError),
+        }
+    }
+
+    Ok(())
+}
+
+/// decrypt the piece of every location found by locate_all to the writer at the same index, skipping
+/// those without one. the rest of every item that was split is located with one more pass over reader
+/// shared by all of them and follows in order. only a window of the blob is held in memory at once,
+/// content reaches a writer before the mac is checked, discard it if its result is IntegrityError
+pub fn extract_all_located<R:Reader + Seek, W:Writer>(reader: &mut R, writers: &mut [Option<W>], settings: &Settings, locations: &[Location]) -> PlauseResult<Vec<PlauseResult<()>>> {
+    let mut results: Vec<PlauseResult<()>> = Vec::with_capacity(locations.len());
+
+    //the password of every fragment still to be found, with its item's index and fragment
+    let mut passwords: Vec<Vec<u8>>          = Vec::new();
+    let mut owners:    Vec<(uint, Fragment)> = Vec::new();
+
+    for (i, location) in locations.iter().enumerate() {
+        let writer = match writers[i] {
+            Some(ref mut x) => x,
+            None            => {
+                results.push(Ok(()));
+                continue;
+            },
+        };
+
+        results.push(match stream_located(reader, writer, settings, location) {
+            Ok(None)                      => Ok(()),
+            Ok(Some(ref x)) if x.seq == 0 => {
+                for seq in range(1, x.total) {
+                    passwords.push(fragment_password(&location.password, seq));
+                    owners.push((i, Fragment { seq: seq, total: x.total }));
+                }
+
+                Ok(())
+            },
+            Ok(Some(_))                   => Err(IntegrityError),
+            Err(e)                        => Err(e),
+        });
+    }
+
+    if passwords.is_empty() {
+        return Ok(results);
+    }
+
+    try!(reader.seek(0, SeekSet));
+    let rest = try!(locate_all(reader, settings, passwords.as_slice()));
+
+    for (location, &(i, ref fragment)) in rest.iter().zip(owners.iter()) {
+        //once a fragment fails the ones after it are of no use
+        if results[i].is_err() {
+            continue;
+        }
+
+        let writer = match writers[i] {
+            Some(ref mut x) => x,
+            None            => continue,
+        };
+
+        match stream_located(reader, writer, settings, location) {
+            Ok(Some(ref x)) if x == fragment => { },
+            Ok(_)                            => *results.get_mut(i) = Err(IntegrityError),
+            Err(e)                           => *results.get_mut(i) = Err(e),
+        }
+    }
+
+    Ok(results)
+}
+
+/// decrypt the single piece at location to writer, returning which fragment it was
+fn stream_located<R:Reader + Seek, W:Writer>(reader: &mut R, writer: &mut W, settings: &Settings, location: &Location) -> PlauseResult<Option<Fragment>> {
+    let begin = match location.begin {
+        Some(x) => x,
+        None    => return Err(NotFound),
+    };
+
+    match location.gathered {
+        Some(ref x) => return stream_piece(&mut MemReader::new(Vec::new()), writer, settings, &location.key, &location.keystream, x[begin as uint..].to_vec(), false, false),
+        None        => try!(reader.seek(begin as i64, SeekSet)),
+    }
+
+    let mut pending: Vec<u8> = Vec::new();
+    let more = try!(read_window(reader, &mut pending));
+
+    stream_piece(reader, writer, settings, &location.key, &location.keystream, pending, more, false)
+}
+
+/// find the piece unlocked by password in a blob of any size and write it decrypted to writer,
+/// only a window of the blob is held in memory at once.
+/// content reaches writer before the mac is checked, discard it if IntegrityError is returned
+pub fn extract_stream<R:Reader, W:Writer>(reader: &mut R, writer: &mut W, settings: &Settings, password: &Vec<u8>) -> PlauseResult<()> {
+    //a scattered piece reaches across the whole blob, so it is read in full
+    if settings.layout == LayoutScattered {
+        let content = try!(reader.read_to_end());
+        let mut sh  = settings.digest.hasher();
+        let result  = try!(BlobReader::new(content.as_slice()).extract(&mut sh, settings, password));
+
+        try!(writer.write(result.as_slice()));
+        return Ok(());
+    }
+
+    let password  = &stretch(settings, password);
+    let keystream = Keystream::new(settings, password);
+    let prefix    = prefix_ident(&keystream, settings, password);
+
+    let mut buf: Vec<u8> = Vec::new();
+    let mut more  = try!(read_window(reader, &mut buf));
+    let mut begin = 0u;
+
+    loop {
+        match find_needle(buf.as_slice(), prefix.as_slice()) {
+            Some(x) => {
+                begin = x + prefix.len();
+                break;
+            },
+            None if !more => return Err(NotFound),
+            None => {
+                //keep enough of the tail that a needle straddling the window edge is still found
+                let keep = min(buf.len(), prefix.len() - 1);
+                buf  = buf[buf.len() - keep..].to_vec();
+                more = try!(read_window(reader, &mut buf));
+            },
+        }
+    }
+
+    //the rest of a fragmented item can't be searched for without going back over the blob,
+    //so a fragment is refused before any of it reaches writer
+    try!(stream_piece(reader, writer, settings, password, &keystream, buf[begin..].to_vec(), more, true));
+    Ok(())
+}
+
+/// stream a piece's body to writer, leaving out the header and padding if the format has them,
+/// returning which fragment of its item the piece was. a whole piece is wanted when the rest of
+/// an item couldn't be found, a fragment is then FragmentedPiece with nothing written
+fn stream_piece<R:Reader, W:Writer>(reader: &mut R, writer: &mut W, settings: &Settings, password: &Vec<u8>, keystream: &Keystream, pending: Vec<u8>, more: bool, whole: bool) -> PlauseResult<Option<Fragment>> {
+    if !settings.framed {
+        try!(stream_body(reader, writer, settings, password, keystream, pending, more));
+        return Ok(None);
+    }
+
+    let mut frame = if whole { FrameWriter::whole(writer) } else { FrameWriter::new(writer) };
+    try!(stream_body(reader, &mut frame, settings, password, keystream, pending, more));
+    frame.finish()
+}
+
+/// decrypt from the start of a piece body up to its postfix ident, pending being
+/// what has already been read of the body. like open, a postfix whose mac doesn't
+/// verify is taken as part of the body and the next one is tried
+fn stream_body<R:Reader, W:Writer>(reader: &mut R, writer: &mut W, settings: &Settings, password: &Vec<u8>, keystream: &Keystream, pending: Vec<u8>, more: bool) -> PlauseResult<()> {
+    let mut mh        = settings.digest.hasher();
+    let postfix       = postfix_ident(keystream, settings, password);
+    let tag_len       = if settings.authenticate { mac_len(&mh) } else { 0 };
+    let mut hmac      = if settings.authenticate { Some(Hmac::new(&mut mh, settings, password)) } else { None };
+    let mut stream    = keystream.clone();
+    let mut pending   = pending;
+    let mut more      = more;
+    let mut from      = 0u;
+    let mut failed    = false;
+
+    //bytes that could still turn out to be the tag or the start of the postfix are held back
+    let hold = postfix.len() - 1 + tag_len;
+
+    loop {
+        match find_needle(pending[from..], postfix.as_slice()).map(|x| from + x) {
+            Some(end) => {
+                from = end + 1;
+
+                //too close to the start for a tag to precede it
+                if end < tag_len {
+                    failed = true;
+                    continue;
+                }
+
+                let mut body = pending[..end].to_vec();
+                stream.clone().apply(body.as_mut_slice());
+
+                let split = end - tag_len;
+
+                let verified = match hmac {
+                    Some(ref h) => h.check(body[..split], body[split..]),
+                    None        => true,
+                };
+
+                if verified {
+                    try!(writer.write(body[..split]));
+                    return Ok(());
+                }
+
+                failed = true;
+            },
+            None if !more => return Err(if failed { IntegrityError } else { Unterminated }),
+            None => {
+                if pending.len() > hold {
+                    let commit = pending.len() - hold;
+                    let mut chunk = pending[..commit].to_vec();
+                    stream.apply(chunk.as_mut_slice());
+
+                    match hmac {
+                        Some(ref mut h) => h.input(chunk.as_slice()),
+                        None            => { },
+                    }
+
+                    try!(writer.write(chunk.as_slice()));
+                    pending = pending[commit..].to_vec();
+                    from    = if from > commit { from - commit } else { 0 };
+                }
+
+                more = try!(read_window(reader, &mut pending));
+            },
+        }
+    }
+}
+
+/// append up to a window of the blob to buf, false once the blob is exhausted
+fn read_window<R:Reader>(reader: &mut R, buf: &mut Vec<u8>) -> IoResult<bool> {
+    match reader.push(WINDOW_SIZE, buf) {
+        Ok(_)                              => Ok(true),
+        Err(ref e) if e.kind == EndOfFile  => Ok(false),
+        Err(e)                             => Err(e),
+    }
+}
+
+/// position of every occurrence of needle in haystack at or after from
+fn find_all(haystack: &[u8], needle: &[u8], from: uint) -> Vec<uint> {
+    let mut result: Vec<uint> = Vec::new();
+    let mut pos = from;
+
+    loop {
+        match find_needle(haystack[pos..], needle) {
+            Some(x) => {
+                result.push(pos + x);
+                pos += x + 1;
+            },
+            None    => return result,
+        }
+    }
+}
+
+/// position of the first occurrence of needle in haystack
+fn find_needle(haystack: &[u8], needle: &[u8]) -> Option<uint> {
+    let first_letter = needle[0];
+
+    for (i, vi) in haystack.iter().enumerate() {
+        if vi == &first_letter {
+            let mut success: bool = true;
+
+            if i + needle.len() > haystack.len() {
+                return None;
+            }
+
+            for (j, vj) in needle.iter().enumerate() {
+                if &haystack[i + j] != vj {
+                    success = false;
+                    break;
+                }
+            }
+
+            if success {
+                return Some(i);
+            }
+        }
+    }
+
+    None
+}
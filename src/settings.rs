@@ -1,12 +1,68 @@
+use util::concat_vec;
+use digest::{DigestKind, DigestSha256};
+use kdf::KdfParams;
+use keystream::{KeystreamKind, KeystreamCascade, KeystreamCounter};
+use padding::{Padding, NoPadding};
+use decoy::{Decoys, NoDecoys};
+use compress::{Compression, NoCompression};
+use layout::{Layout, LayoutContiguous};
+
 static DEFAULT_PREFIX_SALT:  &'static str = "@7@llc05754261933$uFf3r";
 static DEFAULT_POSTFIX_SALT: &'static str = "11419523LIMin@73pRiv@cY";
+static DEFAULT_MAC_SALT:     &'static str = "5uR3ly@uth3nt1c8713919";
+
+/// format version generation writes, extraction accepts this and every one before it.
+/// 0 is the original cascade with char widened hashing, 1 the counter keystream hashing raw bytes,
+/// 2 adds idents of a fixed length, 3 a header in the piece body so it can be padded
+pub static FORMAT_VERSION: u32 = 3;
+
+/// ident length of format 2, long enough that a stray match in filler never happens
+pub static DEFAULT_IDENT_LEN: uint = 32;
+
+/// fixed ident lengths outside these are refused, shorter idents turn up in filler by chance
+/// and longer ones only cost memory
+pub static MIN_IDENT_LEN: uint = 16;
+pub static MAX_IDENT_LEN: uint = 1024;
+
+/// whether len is a fixed ident length between MIN_IDENT_LEN and MAX_IDENT_LEN
+pub fn valid_ident_len(len: uint) -> bool {
+    len >= MIN_IDENT_LEN && len <= MAX_IDENT_LEN
+}
 
+/// Settings shared by generation and extraction, both sides must agree on these
 #[deriving(Clone,Show)]
 pub struct Settings {
     pub blocksize:    u32,
     pub salt:         Vec<u8>,
     pub salt_prefix:  Vec<u8>,
     pub salt_postfix: Vec<u8>,
+    pub salt_mac:     Vec<u8>,
+    /// format version, the structural choices below default to what it used
+    pub version:      u32,
+    pub digest:       DigestKind,
+    pub keystream:    KeystreamKind,
+    /// hash raw bytes, false reproduces the original char widening so older blobs still extract
+    pub raw_hash:     bool,
+    /// length of the prefix and postfix idents, None makes them as long as their salt and password
+    pub ident_len:    Option<uint>,
+    /// piece bodies start with a header holding the content length
+    pub framed:       bool,
+    /// how much pieces are padded by, only framed pieces are padded
+    pub padding:      Padding,
+    /// pieces under throwaway passwords generated alongside the real ones, never part of the key file
+    pub decoys:       Decoys,
+    /// how items are compressed as they are added, only framed pieces can be compressed
+    pub compression:  Compression,
+    /// number of blob files every item is split across
+    pub volumes:      uint,
+    /// most fragments an item is split into within a blob, 1 keeps every item in a single piece.
+    /// only framed pieces can be fragments, their header records the sequence
+    pub fragments:    uint,
+    /// how pieces' bytes map onto the block
+    pub layout:       Layout,
+    pub kdf:          Option<KdfParams>,
+    /// append an encrypted mac to every piece so tampering and wrong passwords are detected
+    pub authenticate: bool,
 }
 
 impl Settings {
@@ -16,6 +72,39 @@ impl Settings {
             salt:         String::from_str(salt).into_bytes(),
             salt_prefix:  format!("{}{}", DEFAULT_PREFIX_SALT,  salt).into_bytes(),
             salt_postfix: format!("{}{}", DEFAULT_POSTFIX_SALT, salt).into_bytes(),
+            salt_mac:     format!("{}{}", DEFAULT_MAC_SALT,     salt).into_bytes(),
+            version:      FORMAT_VERSION,
+            digest:       DigestSha256,
+            keystream:    KeystreamCounter,
+            raw_hash:     true,
+            ident_len:    Some(DEFAULT_IDENT_LEN),
+            framed:       true,
+            padding:      NoPadding,
+            decoys:       NoDecoys,
+            compression:  NoCompression,
+            volumes:      1,
+            fragments:    1,
+            layout:       LayoutContiguous,
+            kdf:          Some(KdfParams::default()),
+            authenticate: true,
         }
     }
+
+    /// change the salt, the prefix and postfix salts are derived from it
+    pub fn set_salt(&mut self, salt: Vec<u8>) {
+        self.salt_prefix  = concat_vec(DEFAULT_PREFIX_SALT.as_bytes().to_vec(),  salt.clone());
+        self.salt_postfix = concat_vec(DEFAULT_POSTFIX_SALT.as_bytes().to_vec(), salt.clone());
+        self.salt_mac     = concat_vec(DEFAULT_MAC_SALT.as_bytes().to_vec(),     salt.clone());
+        self.salt         = salt;
+    }
+
+    /// switch to format version and the structural choices it was generated with,
+    /// versions are frozen once released
+    pub fn set_version(&mut self, version: u32) {
+        self.version   = version;
+        self.keystream = if version >= 1 { KeystreamCounter } else { KeystreamCascade };
+        self.raw_hash  = version >= 1;
+        self.ident_len = if version >= 2 { Some(DEFAULT_IDENT_LEN) } else { None };
+        self.framed    = version >= 3;
+    }
 }
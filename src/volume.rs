@@ -0,0 +1,51 @@
+use item::Item;
+use compress::decompress;
+use error::PlauseResult;
+use util::concat_vec;
+
+/// password of an item's chunk in volume index of count, a single volume uses the password itself
+pub fn volume_password(password: &Vec<u8>, index: uint, count: uint) -> Vec<u8> {
+    match count {
+        1 => password.clone(),
+        _ => concat_vec(password.clone(), format!(":{}", index).into_bytes()),
+    }
+}
+
+/// path of volume index of count for a blob written to path, a single volume is path itself
+pub fn volume_path(path: &Path, index: uint, count: uint) -> Path {
+    match count {
+        1 => path.clone(),
+        _ => Path::new(format!("{}.{}", path.display(), index)),
+    }
+}
+
+/// length of the largest chunk content of len bytes is split into over count volumes
+pub fn chunk_len(len: uint, count: uint) -> uint {
+    (len + count - 1) / count
+}
+
+/// the part of content that goes in volume index of count, earlier volumes take the remainder
+fn chunk(content: &[u8], index: uint, count: uint) -> &[u8] {
+    let base  = content.len() / count;
+    let extra = content.len() % count;
+    let start = index * base + if index < extra { index } else { extra };
+    let len   = base + if index < extra { 1 } else { 0 };
+
+    content[start..start + len]
+}
+
+/// the chunk of every item held by volume index of count, under its derived password.
+/// compressed items are split uncompressed and each chunk compressed again so every
+/// piece can be extracted on its own
+pub fn chunk_items(items: &Vec<Item>, index: uint, count: uint) -> PlauseResult<Vec<Item>> {
+    let mut result: Vec<Item> = Vec::with_capacity(items.len());
+
+    for item in items.iter() {
+        let content  = try!(decompress(item.compression.flags(), item.content.clone()));
+        let password = volume_password(&item.password, index, count);
+
+        result.push(Item::compressed(&password, &chunk(content.as_slice(), index, count).to_vec(), item.compression.clone()));
+    }
+
+    Ok(result)
+}
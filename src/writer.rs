@@ -0,0 +1,202 @@
+extern crate "rust-crypto" as crypto;
+
+use std::cmp::min;
+use std::io::Writer;
+use crypto::digest::Digest;
+use settings::Settings;
+use item::Item;
+use piece::Piece;
+use alloc::Allocator;
+use error::{PlauseResult, PlauseError, NoSpace};
+use random::RandomSource;
+use decoy::decoy;
+use fragment::fragment_items;
+use frame::Fragment;
+use kdf::stretch;
+use layout::LayoutScattered;
+
+/// how much filler is generated at a time
+static FILL_CHUNK: uint = 65536;
+
+/// BlobWriter lays staged items out as pieces within a block of random filler.
+/// the layout is decided up front so the block can be streamed out in order
+/// without ever holding blocksize bytes in memory
+pub struct BlobWriter<'a> {
+    items:     &'a Vec<Item>,
+    /// fragments of the items that were split
+    fragments: Vec<Item>,
+    files:     Vec<Piece>,
+    /// what every piece in files holds
+    held:      Vec<Held>,
+}
+
+/// Held is the content of a piece, by index into the items or fragments
+enum Held {
+    HeldItem(uint),
+    HeldFragment(uint),
+    /// a decoy of this many zero bytes
+    HeldDecoy(uint),
+}
+
+impl<'a> BlobWriter<'a> {
+    pub fn new<D:Digest, R:RandomSource>(sh: &mut D, rng: &mut R, settings: &Settings, items: &'a Vec<Item>) -> PlauseResult<BlobWriter<'a>> {
+        let mut alloc = Allocator::new(settings.blocksize);
+        let mut files:     Vec<Piece> = Vec::new();
+        let mut held:      Vec<Held>  = Vec::new();
+        let mut fragments: Vec<Item>  = Vec::new();
+        //content length and fragment of every real piece, what decoys are modelled on
+        let mut placed: Vec<(uint, Option<Fragment>)> = Vec::new();
+
+        for (i, item) in items.iter().enumerate() {
+            if settings.fragments < 2 || !settings.framed {
+                files.push(try!(Piece::new(sh, rng, &mut alloc, settings, item)));
+                held.push(HeldItem(i));
+                placed.push((item.content.len(), None));
+                continue;
+            }
+
+            let (pieces, parts) = try!(place_fragments(sh, rng, &mut alloc, settings, item));
+            let total = parts.len();
+
+            for (seq, (piece, part)) in pieces.into_iter().zip(parts.into_iter()).enumerate() {
+                files.push(piece);
+                held.push(HeldFragment(fragments.len()));
+                placed.push((part.content.len(), Some(Fragment { seq: seq, total: total })));
+                fragments.push(part);
+            }
+        }
+
+        //decoys are placed after every real item so they only ever take leftover space,
+        //once one doesn't fit the block is full enough and the rest aren't tried
+        for _ in range(0, settings.decoys.count(rng, items.len())) {
+            let decoy = decoy(rng, settings, placed.as_slice());
+
+            match Piece::with_key(sh, rng, &mut alloc, settings, decoy.key, decoy.len, decoy.fragment) {
+                Ok(piece) => {
+                    files.push(piece);
+                    held.push(HeldDecoy(decoy.len));
+                },
+                Err(_)    => break,
+            }
+        }
+
+        Ok(BlobWriter {
+            items:     items,
+            fragments: fragments,
+            files:     files,
+            held:      held,
+        })
+    }
+
+    /// write the whole block in a single pass, filler and pieces in order of position.
+    /// pieces are sorted once and every buffer is reused so this is linear in blocksize and item count
+    pub fn write_to<R:RandomSource, W:Writer>(&self, rng: &mut R, settings: &Settings, writer: &mut W) -> PlauseResult<()> {
+        if settings.layout == LayoutScattered {
+            return self.write_scattered(rng, settings, writer);
+        }
+
+        let mut order: Vec<uint> = range(0, self.files.len()).collect();
+        order.sort_by(|a, b| self.files[*a].start_pos.cmp(&self.files[*b].start_pos));
+
+        let mut filler: Vec<u8> = Vec::from_elem(min(settings.blocksize as uint, FILL_CHUNK), 0u8);
+        let mut pos: u32 = 0;
+
+        for &i in order.iter() {
+            let file = &self.files[i];
+
+            try!(write_filler(rng, writer, filler.as_mut_slice(), (file.start_pos - pos) as uint));
+            match self.held[i] {
+                HeldItem(x)     => try!(write_item(settings, file, &self.items[x], writer)),
+                HeldFragment(x) => try!(write_item(settings, file, &self.fragments[x], writer)),
+                HeldDecoy(len)  => {
+                    let content: Vec<u8> = Vec::from_elem(len, 0u8);
+                    try!(file.write(settings, 0, content.as_slice(), writer));
+                },
+            }
+            pos = file.end_pos;
+        }
+
+        write_filler(rng, writer, filler.as_mut_slice(), (settings.blocksize - pos) as uint)
+    }
+
+    /// write a block whose pieces are spread over cells, it is assembled in memory
+    /// as every piece reaches across the whole of it
+    fn write_scattered<R:RandomSource, W:Writer>(&self, rng: &mut R, settings: &Settings, writer: &mut W) -> PlauseResult<()> {
+        let mut block: Vec<u8> = Vec::from_elem(settings.blocksize as uint, 0u8);
+        rng.fill(block.as_mut_slice());
+
+        for (file, held) in self.files.iter().zip(self.held.iter()) {
+            match *held {
+                HeldItem(x)     => try!(file.scatter(settings, self.items[x].compression.flags(), self.items[x].content.as_slice(), block.as_mut_slice())),
+                HeldFragment(x) => try!(file.scatter(settings, self.fragments[x].compression.flags(), self.fragments[x].content.as_slice(), block.as_mut_slice())),
+                HeldDecoy(len)  => {
+                    let content: Vec<u8> = Vec::from_elem(len, 0u8);
+                    try!(file.scatter(settings, 0, content.as_slice(), block.as_mut_slice()));
+                },
+            }
+        }
+
+        try!(writer.write(block.as_slice()));
+        Ok(())
+    }
+}
+
+/// pieces for item split into as few fragments as will fit, from two up to settings.fragments.
+/// every count is tried on a copy of the allocator so a failed attempt leaves no trace
+fn place_fragments<D:Digest, R:RandomSource>(sh: &mut D, rng: &mut R, alloc: &mut Allocator, settings: &Settings, item: &Item) -> PlauseResult<(Vec<Piece>, Vec<Item>)> {
+    let mut keys: Vec<Vec<u8>> = Vec::new();
+    let mut last: PlauseError  = NoSpace(item.content.len());
+
+    for count in range(2, settings.fragments + 1) {
+        let parts = try!(fragment_items(item, count));
+
+        //a fragment's password doesn't depend on the count, so each is only stretched once
+        while keys.len() < count {
+            let key = stretch(settings, &parts[keys.len()].password);
+            keys.push(key);
+        }
+
+        let mut trial = alloc.clone();
+        let mut pieces: Vec<Piece> = Vec::with_capacity(count);
+        let mut failed: Option<PlauseError> = None;
+
+        for (seq, part) in parts.iter().enumerate() {
+            let fragment = Fragment { seq: seq, total: count };
+
+            match Piece::with_key(sh, rng, &mut trial, settings, keys[seq].clone(), part.content.len(), Some(fragment)) {
+                Ok(piece) => pieces.push(piece),
+                Err(e)    => {
+                    failed = Some(e);
+                    break;
+                },
+            }
+        }
+
+        match failed {
+            Some(e) => last = e,
+            None    => {
+                *alloc = trial;
+                return Ok((pieces, parts));
+            },
+        }
+    }
+
+    Err(last)
+}
+
+fn write_item<W:Writer>(settings: &Settings, file: &Piece, item: &Item, writer: &mut W) -> PlauseResult<()> {
+    file.write(settings, item.compression.flags(), item.content.as_slice(), writer)
+}
+
+fn write_filler<R:RandomSource, W:Writer>(rng: &mut R, writer: &mut W, buf: &mut [u8], len: uint) -> PlauseResult<()> {
+    let mut left = len;
+
+    while left > 0 {
+        let n = min(left, buf.len());
+        rng.fill(buf[mut ..n]);
+        try!(writer.write(buf[..n]));
+        left -= n;
+    }
+
+    Ok(())
+}
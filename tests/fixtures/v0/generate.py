@@ -0,0 +1,69 @@
+#!/usr/bin/env python3
+# writes output.enc and pass.key exactly as the original release (format version 0) lays them out,
+# that release no longer builds with a current compiler so its algorithm is reproduced here:
+#   hash(x)  = sha-256 of x with every byte widened to a char, so bytes from 0x80 up hash as two
+#   hxor     = a fresh cascade of 16 digests of salt+password+[n], each rehashed with the salt
+#              every 32 bytes, content xored with all of them
+#   piece    = hxor(prefix salt+password) + hxor(content) + hxor(postfix salt+password)
+#   filler   = bytes in 0..254 before and between pieces, after the last piece only the first
+#              byte is random and the rest stay zero as the original block writer left them
+#   pass.key = the salt then one password per line
+import hashlib, os, random, string, sys
+
+SALT      = b"saltysaltsalt"
+PREFIX    = b"@7@llc05754261933$uFf3r" + SALT
+POSTFIX   = b"11419523LIMin@73pRiv@cY" + SALT
+BLOCKSIZE = 4096
+ITEMS     = ["plain.0", "plain.1"]
+
+def chash(x):
+    return hashlib.sha256(bytes(x).decode("latin-1").encode("utf-8")).digest()
+
+def hxor(password, content):
+    cascade = [chash(SALT + password + bytes([n + 1])) for n in range(16)]
+    result  = bytearray()
+
+    for i, c in enumerate(content):
+        if i % 32 == 0:
+            cascade = [chash(SALT + v) for v in cascade]
+
+        for v in cascade:
+            c ^= v[i % 32]
+
+        result.append(c)
+
+    return bytes(result)
+
+def piece(password, content):
+    return hxor(password, PREFIX + password) + hxor(password, content) + hxor(password, POSTFIX + password)
+
+def main():
+    here = os.path.dirname(os.path.abspath(__file__))
+    rng  = random.SystemRandom()
+
+    passwords = [''.join(rng.choice(string.ascii_letters + string.digits) for _ in range(24)).encode() for _ in ITEMS]
+    pieces    = [piece(p, open(os.path.join(here, name), "rb").read()) for p, name in zip(passwords, ITEMS)]
+
+    #random non overlapping starts, as the original found by retrying
+    while True:
+        starts = [rng.randrange(BLOCKSIZE) for _ in pieces]
+        spans  = sorted((s, s + len(p), p) for s, p in zip(starts, pieces))
+
+        if spans[-1][1] < BLOCKSIZE and all(a[1] < b[0] for a, b in zip(spans, spans[1:])):
+            break
+
+    blob = bytearray(BLOCKSIZE)
+    pos  = 0
+
+    for start, end, p in spans:
+        blob[pos:start] = bytes(rng.randrange(255) for _ in range(start - pos))
+        blob[start:end] = p
+        pos = end
+
+    blob[pos] = rng.randrange(255)
+
+    open(os.path.join(here, "output.enc"), "wb").write(bytes(blob))
+    open(os.path.join(here, "pass.key"), "wb").write(SALT + b"\n" + b"".join(p + b"\n" for p in passwords))
+
+if __name__ == "__main__":
+    main()
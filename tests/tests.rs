@@ -0,0 +1,838 @@
+#![feature(slicing_syntax)]
+
+extern crate plause;
+extern crate flate;
+extern crate "rust-crypto" as crypto;
+
+use std::io::{File, BufferedReader, MemReader, MemWriter, IoResult, Seek, SeekStyle};
+use std::rand::IsaacRng;
+use crypto::digest::Digest;
+use plause::Plause;
+use plause::digest::{DigestKind, DigestSha256, DigestSha512, DigestBlake2b};
+use plause::settings::FORMAT_VERSION;
+use plause::alloc::Allocator;
+use plause::matcher::Matcher;
+use plause::random::{RandomSource, seeded_random};
+use plause::keystream::{Keystream, KeystreamCascade, KeystreamCounter};
+use plause::{Settings, KeyFile, KdfParams};
+use plause::decoy::DecoyCount;
+use plause::volume::volume_password;
+use plause::duress::{diary, shortest_entry};
+use plause::layout::{LayoutDerived, LayoutScattered};
+use plause::padding::{Padding, PadPowerOfTwo, PadBuckets, PadRandom};
+use plause::compress::{CompressDeflate, FLAG_DEFLATE, DEFLATE_BLOCK, decompress};
+use plause::PlauseResult;
+use plause::error::{NotFound, IntegrityError, InvalidPassword, NoSpace, FragmentedPiece, MalformedKeyFile};
+
+static ROUNDS: u64 = 200;
+
+/// allocate random lengths until the block is full, returning every range handed out
+fn fill_block<R: RandomSource>(rng: &mut R, alloc: &mut Allocator, blocksize: u32) -> Vec<(u32, u32)> {
+    let mut taken: Vec<(u32, u32)> = Vec::new();
+
+    loop {
+        let len = rng.below(blocksize / 4 + 1) + 1;
+
+        match alloc.alloc(rng, len) {
+            Some(start) => taken.push((start, start + len)),
+            None        => {
+                //failing is only allowed when no gap could have held it
+                let gap = alloc.largest_gap();
+                assert!(gap < len);
+
+                if gap == 0 {
+                    return taken;
+                }
+
+                let start = alloc.alloc(rng, gap).expect("alloc refused a length equal to the largest gap");
+                taken.push((start, start + gap));
+            },
+        }
+    }
+}
+
+/// a seeded plause without key stretching so tests don't spend their time in scrypt
+fn test_plause(blocksize: u32, seed: u64) -> Plause<IsaacRng> {
+    let mut plause = Plause::with_seed(blocksize, "saltysaltsalt", seed);
+    plause.settings.kdf = None;
+    plause
+}
+
+/// content of len bytes that differs for every seed
+fn test_content(seed: u64, len: uint) -> Vec<u8> {
+    let mut rng = seeded_random(seed);
+    let mut content: Vec<u8> = Vec::from_elem(len, 0u8);
+    rng.fill(content.as_mut_slice());
+    content
+}
+
+/// a plause reading the blob of plause under the settings of its key file once written and read back,
+/// so whatever is under test has to survive the key file as well
+fn reopen<R: RandomSource>(plause: &Plause<R>) -> Plause<IsaacRng> {
+    let mut written = MemWriter::new();
+    plause.keyfile().write(&mut written).unwrap();
+    let keyfile = KeyFile::read(&mut BufferedReader::new(MemReader::new(written.unwrap()))).unwrap();
+
+    let mut reader = test_plause(plause.settings.blocksize, 0);
+    reader.set_keyfile(&keyfile);
+    reader.import(&mut MemReader::new(plause.content.clone())).unwrap();
+    reader
+}
+
+/// CountingReader is a seekable blob that tallies how many of its bytes are read
+struct CountingReader {
+    inner: MemReader,
+    read:  uint,
+}
+
+impl Reader for CountingReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let len = try!(self.inner.read(buf));
+        self.read += len;
+        Ok(len)
+    }
+}
+
+impl Seek for CountingReader {
+    fn tell(&self) -> IoResult<u64> {
+        self.inner.tell()
+    }
+
+    fn seek(&mut self, pos: i64, style: SeekStyle) -> IoResult<()> {
+        self.inner.seek(pos, style)
+    }
+}
+
+/// the content of every password comes back out of the blob of plause, both in memory and streamed
+fn assert_extracts<R: RandomSource>(plause: &Plause<R>, passwords: &[Vec<u8>], contents: &[Vec<u8>]) {
+    let reader = reopen(plause);
+
+    for (password, content) in passwords.iter().zip(contents.iter()) {
+        assert!(reader.extract(password).unwrap() == *content);
+
+        let mut streamed = MemWriter::new();
+        reader.extract_to(&mut MemReader::new(reader.content.clone()), &mut streamed, password).unwrap();
+        assert!(streamed.get_ref() == content.as_slice());
+    }
+}
+
+#[test]
+fn alloc_never_overlaps_or_exceeds_block() {
+    for seed in range(0, ROUNDS) {
+        let mut rng = seeded_random(seed);
+        let blocksize = rng.below(4096) + 1;
+        let mut alloc = Allocator::new(blocksize);
+
+        let mut taken = fill_block(&mut rng, &mut alloc, blocksize);
+        taken.sort();
+
+        for &(s, e) in taken.iter() {
+            assert!(s < e);
+            assert!(e <= blocksize);
+        }
+
+        for w in taken.windows(2) {
+            let (_, prev_end)   = w[0];
+            let (next_start, _) = w[1];
+            assert!(prev_end <= next_start, "ranges {} and {} overlap", w[0], w[1]);
+        }
+    }
+}
+
+#[test]
+fn alloc_fills_block_completely() {
+    for seed in range(0, ROUNDS) {
+        let mut rng = seeded_random(seed);
+        let blocksize = rng.below(4096) + 1;
+        let mut alloc = Allocator::new(blocksize);
+
+        let taken = fill_block(&mut rng, &mut alloc, blocksize);
+        let used  = taken.iter().fold(0u32, |acc, &(s, e)| acc + (e - s));
+
+        assert_eq!(used, blocksize);
+        assert_eq!(alloc.free_space(), 0);
+    }
+}
+
+#[test]
+fn alloc_exact_fit_succeeds() {
+    let mut rng = seeded_random(0);
+    let mut alloc = Allocator::new(100);
+
+    assert_eq!(alloc.alloc(&mut rng, 100), Some(0));
+    assert_eq!(alloc.alloc(&mut rng, 1), None);
+}
+
+#[test]
+fn alloc_reaches_every_position() {
+    let mut rng = seeded_random(1);
+    let mut seen = [false, ..8];
+
+    for _ in range(0u, 1000) {
+        let mut alloc = Allocator::new(10);
+        seen[alloc.alloc(&mut rng, 3).unwrap() as uint] = true;
+    }
+
+    assert!(seen.iter().all(|&x| x));
+}
+
+#[test]
+fn reserve_rejects_taken_ranges() {
+    let mut alloc = Allocator::new(100);
+
+    assert!(alloc.reserve(10, 20));
+    assert!(!alloc.reserve(25, 10));
+    assert!(!alloc.reserve(0, 11));
+    assert!(!alloc.reserve(95, 10));
+    assert!(alloc.reserve(0, 10));
+    assert!(alloc.reserve(30, 70));
+    assert_eq!(alloc.free_space(), 0);
+}
+
+#[test]
+fn keystream_seek_matches_sequential() {
+    for kind in [KeystreamCascade, KeystreamCounter].iter() {
+        let mut settings = Settings::new(4096, "saltysaltsalt");
+        settings.keystream = kind.clone();
+
+        let keystream = Keystream::new(&settings, &b"password".to_vec());
+        let mut whole: Vec<u8> = Vec::from_elem(1000, 0u8);
+        keystream.clone().apply(whole.as_mut_slice());
+
+        let mut rng = seeded_random(0);
+
+        for _ in range(0, ROUNDS) {
+            let start = rng.below(900) as uint;
+            let len   = rng.below(100) as uint;
+
+            let mut part: Vec<u8> = Vec::from_elem(len, 0u8);
+            let mut seeked = keystream.clone();
+            seeked.seek(start);
+            seeked.apply(part.as_mut_slice());
+
+            assert!(part.as_slice() == whole[start..start + len]);
+        }
+    }
+}
+
+#[test]
+fn keystream_parallel_matches_serial() {
+    let settings  = Settings::new(4096, "saltysaltsalt");
+    let keystream = Keystream::new(&settings, &b"password".to_vec());
+
+    //large enough to be split over threads in one call, small enough per call to stay serial
+    let mut whole: Vec<u8> = Vec::from_elem(4 << 20, 0u8);
+    keystream.clone().apply(whole.as_mut_slice());
+
+    let mut serial = keystream.clone();
+
+    for chunk in whole.as_slice().chunks(65536) {
+        let mut part: Vec<u8> = Vec::from_elem(chunk.len(), 0u8);
+        serial.apply(part.as_mut_slice());
+
+        assert!(part.as_slice() == chunk);
+    }
+}
+
+#[test]
+fn scattered_round_trip() {
+    let mut plause = test_plause(65536, 0);
+    plause.settings.layout = LayoutScattered;
+
+    let first  = test_content(1, 10000);
+    let second = test_content(2, 777);
+    plause.add(&b"first".to_vec(), &first).unwrap();
+    plause.add(&b"second".to_vec(), &second).unwrap();
+    plause.gen().unwrap();
+
+    assert!(plause.extract(&b"first".to_vec()).unwrap() == first);
+    assert!(plause.extract(&b"second".to_vec()).unwrap() == second);
+
+    let mut streamed = MemWriter::new();
+    plause.extract_to(&mut MemReader::new(plause.content.clone()), &mut streamed, &b"second".to_vec()).unwrap();
+    assert!(streamed.get_ref() == second.as_slice());
+
+    let mut reader    = MemReader::new(plause.content.clone());
+    let locations     = plause.locate_all(&mut reader, &[b"first".to_vec()]).unwrap();
+    let mut located   = MemWriter::new();
+    plause.extract_located(&mut reader, &mut located, &locations[0]).unwrap();
+    assert!(located.get_ref() == first.as_slice());
+}
+
+#[test]
+fn scattered_reads_only_its_cells() {
+    let mut plause = test_plause(1 << 20, 6);
+    plause.settings.layout = LayoutScattered;
+
+    let contents: Vec<Vec<u8>> = range(0u64, 8).map(|i| test_content(6 + i, 3000)).collect();
+
+    for (i, content) in contents.iter().enumerate() {
+        plause.add(&format!("piece{}", i).into_bytes(), content).unwrap();
+    }
+
+    plause.gen().unwrap();
+
+    for (i, content) in contents.iter().enumerate() {
+        assert!(plause.extract(&format!("piece{}", i).into_bytes()).unwrap() == *content);
+    }
+
+    //the last piece is found by walking past the cells of the others, not by reading the blob
+    let mut reader  = CountingReader { inner: MemReader::new(plause.content.clone()), read: 0 };
+    let locations   = plause.locate_all(&mut reader, &[b"piece7".to_vec()]).unwrap();
+    assert!(reader.read < plause.content.len() / 16);
+
+    let mut located = MemWriter::new();
+    plause.extract_located(&mut reader, &mut located, &locations[0]).unwrap();
+    assert!(located.get_ref() == contents[7].as_slice());
+}
+
+#[test]
+fn derived_round_trip() {
+    let mut plause = test_plause(65536, 3);
+    plause.settings.layout = LayoutDerived;
+
+    let first  = test_content(4, 20000);
+    let second = test_content(5, 300);
+    plause.add(&b"first".to_vec(), &first).unwrap();
+    plause.add(&b"second".to_vec(), &second).unwrap();
+    plause.gen().unwrap();
+
+    assert!(plause.extract(&b"first".to_vec()).unwrap() == first);
+    assert!(plause.extract(&b"second".to_vec()).unwrap() == second);
+
+    let mut reader  = MemReader::new(plause.content.clone());
+    let locations   = plause.locate_all(&mut reader, &[b"second".to_vec(), b"first".to_vec()]).unwrap();
+    let mut located = MemWriter::new();
+    plause.extract_located(&mut reader, &mut located, &locations[1]).unwrap();
+    assert!(located.get_ref() == first.as_slice());
+
+    match plause.extract(&b"wrong".to_vec()) {
+        Err(NotFound) => { },
+        _             => fail!("a wrong password found a derived piece"),
+    }
+
+    let mut reader = MemReader::new(plause.content.clone());
+    let locations  = plause.locate_all(&mut reader, &[b"wrong".to_vec()]).unwrap();
+    assert!(locations[0].begin.is_none());
+}
+
+#[test]
+fn derived_fits_whole_block() {
+    //two idents, the header and the mac take 105 bytes, the piece is exactly the blocksize
+    for seed in range(0, 20u64) {
+        let mut plause = test_plause(4096, seed);
+        plause.settings.layout = LayoutDerived;
+
+        let content = test_content(seed, 4096 - 105);
+        plause.add(&b"whole".to_vec(), &content).unwrap();
+        plause.gen().unwrap();
+
+        assert!(plause.extract(&b"whole".to_vec()).unwrap() == content);
+    }
+}
+
+#[test]
+fn placed_pieces_end_where_their_header_says() {
+    //a piece found without a scan is cut off at the length its header and padding give
+    let paddings = vec![PadPowerOfTwo, PadRandom(50), PadBuckets(vec![4096, 16384])];
+
+    for layout in [LayoutDerived, LayoutScattered].iter() {
+        for padding in paddings.iter() {
+            let mut plause = test_plause(65536, 9);
+            plause.settings.layout  = *layout;
+            plause.settings.padding = padding.clone();
+
+            let content = test_content(9, 3000);
+            plause.add(&b"padded".to_vec(), &content).unwrap();
+            plause.gen().unwrap();
+
+            assert!(plause.extract(&b"padded".to_vec()).unwrap() == content);
+
+            let mut reader  = MemReader::new(plause.content.clone());
+            let locations   = plause.locate_all(&mut reader, &[b"padded".to_vec()]).unwrap();
+            let mut located = MemWriter::new();
+            plause.extract_located(&mut reader, &mut located, &locations[0]).unwrap();
+            assert!(located.get_ref() == content.as_slice());
+        }
+    }
+}
+
+#[test]
+fn key_file_rejects_unreadable_passwords() {
+    for password in [b"!version=3".to_vec(), b"two\nlines".to_vec()].iter() {
+        let mut plause = test_plause(4096, 0);
+
+        match plause.add(password, &b"content".to_vec()) {
+            Err(InvalidPassword) => { },
+            _                    => fail!("a password the key file can't hold was accepted"),
+        }
+
+        let keyfile = KeyFile::new(&plause.settings, &vec![password.clone()]);
+        assert!(keyfile.write(&mut MemWriter::new()).is_err());
+    }
+}
+
+#[test]
+fn same_seed_same_blob() {
+    let content = b"the same seed always gives the same blob".to_vec();
+    let blobs: Vec<Vec<u8>> = range(0u, 2).map(|_| {
+        let mut plause = test_plause(4096, 7);
+        plause.add(&b"golden".to_vec(), &content).unwrap();
+        plause.gen().unwrap();
+        plause.content
+    }).collect();
+
+    assert!(blobs[0] == blobs[1]);
+
+    //pins the seeded random source, placement and piece encoding of the current format
+    let mut sh = DigestSha256.hasher();
+    sh.input(blobs[0].as_slice());
+    assert_eq!(sh.result_str().as_slice(), "e4387bd7683773d31fe525f5a1371e9c0e26aaa3ee1a7752db74f658fab7b277");
+}
+
+#[test]
+fn kdf_rejects_costs_scrypt_refuses() {
+    assert!(KdfParams::from_str("14:8:1").is_some());
+    assert!(KdfParams::from_str("16:1:1").is_none());
+    assert!(KdfParams::from_str("10:1024:1048576").is_none());
+    assert!(KdfParams::from_str("24:8:1").is_none());
+    assert!(KdfParams::from_str("0:8:1").is_none());
+    assert!(KdfParams::from_str("1:1:1073741822").is_none());
+    assert!(KdfParams::from_str("14:8:16").is_some());
+    assert!(KdfParams::from_str("14:8:17").is_none());
+    assert!(KdfParams::from_str("20:8:1").is_none());
+}
+
+/// a damaged blob or wrong password must be refused both in memory and while streaming, never decrypted to garbage
+fn assert_refused<R: RandomSource>(plause: &mut Plause<R>, blob: Vec<u8>, password: &Vec<u8>) {
+    fn refused<T>(result: PlauseResult<T>) -> bool {
+        match result {
+            Err(NotFound) | Err(IntegrityError) => true,
+            _                                   => false,
+        }
+    }
+
+    plause.content = blob.clone();
+    assert!(refused(plause.extract(password)));
+    assert!(refused(plause.extract_to(&mut MemReader::new(blob), &mut MemWriter::new(), password)));
+}
+
+#[test]
+fn mac_refuses_damage_and_wrong_passwords() {
+    let mut plause = test_plause(8192, 11);
+    let password   = b"authentic".to_vec();
+    plause.add(&password, &test_content(12, 3000)).unwrap();
+    plause.gen().unwrap();
+
+    let blob  = plause.content.clone();
+    let begin = plause.locate_all(&mut MemReader::new(blob.clone()), &[password.clone()]).unwrap()[0].begin.unwrap() as uint;
+
+    let mut flipped = blob.clone();
+    *flipped.get_mut(begin + 100) ^= 0x10;
+    assert_refused(&mut plause, flipped, &password);
+
+    let mut truncated = blob[..begin + 500].to_vec();
+    truncated.push_all(blob[begin + 600..]);
+    assert_refused(&mut plause, truncated, &password);
+
+    assert_refused(&mut plause, blob, &b"forged".to_vec());
+}
+
+/// a file of the version 0 fixture, a blob and key file in the original release's format
+fn v0_fixture(name: &str) -> Vec<u8> {
+    File::open(&Path::new(format!("tests/fixtures/v0/{}", name))).read_to_end().unwrap()
+}
+
+fn v0_keyfile() -> KeyFile {
+    KeyFile::read(&mut BufferedReader::new(MemReader::new(v0_fixture("pass.key")))).unwrap()
+}
+
+#[test]
+fn v0_blob_extracts() {
+    let keyfile    = v0_keyfile();
+    let mut plause = test_plause(4096, 0);
+    plause.set_keyfile(&keyfile);
+    plause.import(&mut MemReader::new(v0_fixture("output.enc"))).unwrap();
+
+    assert_eq!(keyfile.version, 0);
+    assert!(plause.extract(&keyfile.passwords[0]).unwrap() == v0_fixture("plain.0"));
+    assert!(plause.extract(&keyfile.passwords[1]).unwrap() == v0_fixture("plain.1"));
+}
+
+#[test]
+fn v0_blob_migrates() {
+    let mut plause = test_plause(4096, 0);
+    plause.migrate(&mut MemReader::new(v0_fixture("output.enc")), &v0_keyfile()).unwrap();
+    plause.gen().unwrap();
+
+    let mut written = MemWriter::new();
+    plause.keyfile().write(&mut written).unwrap();
+
+    let keyfile    = KeyFile::read(&mut BufferedReader::new(MemReader::new(written.unwrap()))).unwrap();
+    let mut reader = test_plause(4096, 1);
+    reader.set_keyfile(&keyfile);
+    reader.import(&mut MemReader::new(plause.content.clone())).unwrap();
+
+    assert_eq!(keyfile.version, FORMAT_VERSION);
+    assert!(keyfile.passwords == v0_keyfile().passwords);
+    assert!(reader.extract(&keyfile.passwords[0]).unwrap() == v0_fixture("plain.0"));
+    assert!(reader.extract(&keyfile.passwords[1]).unwrap() == v0_fixture("plain.1"));
+}
+
+#[test]
+fn decoys_stop_when_the_block_is_full() {
+    let mut plause = test_plause(4096, 21);
+    plause.settings.decoys = DecoyCount(1 << 40);
+
+    let content = test_content(22, 100);
+    plause.add(&b"real".to_vec(), &content).unwrap();
+    plause.gen().unwrap();
+
+    assert!(plause.extract(&b"real".to_vec()).unwrap() == content);
+
+    //decoys posing as fragments beside a fragmented item
+    plause.settings.fragments = 3;
+    plause.gen().unwrap();
+
+    assert!(plause.extract(&b"real".to_vec()).unwrap() == content);
+}
+
+/// stage real content and a duress item as large as duress_room allows, then generate every volume
+fn gen_with_room<R: RandomSource>(plause: &mut Plause<R>, seed: u64) -> PlauseResult<()> {
+    try!(plause.add(&b"real".to_vec(), &test_content(seed, 1500)));
+
+    let room = try!(plause.duress_room(1, 24));
+    assert!(try!(plause.duress_diary(24)).len() <= room);
+
+    let password = plause.random_pass(24);
+    try!(plause.add(&password, &test_content(seed + 1, room)));
+
+    for i in range(0, plause.settings.volumes) {
+        try!(plause.gen_volume_to(i, &mut MemWriter::new()));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn duress_room_always_fits() {
+    for seed in range(0u64, 8) {
+        for variant in range(0u, 7) {
+            let mut plause = test_plause(8192, seed);
+
+            match variant {
+                1 => plause.settings.padding   = PadPowerOfTwo,
+                2 => plause.settings.padding   = PadRandom(50),
+                3 => plause.settings.fragments = 3,
+                4 => plause.settings.volumes   = 2,
+                5 => plause.settings.layout    = LayoutScattered,
+                6 => plause.settings.layout    = LayoutDerived,
+                _ => { },
+            }
+
+            gen_with_room(&mut plause, seed).unwrap();
+        }
+    }
+}
+
+#[test]
+fn duress_room_refuses_a_full_block() {
+    let mut plause = test_plause(256, 23);
+    plause.add(&b"real".to_vec(), &test_content(23, 200)).unwrap();
+
+    match plause.duress_room(1, 24) {
+        Err(NoSpace(..)) => { },
+        _                => fail!("a full block still had room"),
+    }
+}
+
+#[test]
+fn compressed_blocks_stay_bounded() {
+    //a block inflating past DEFLATE_BLOCK is refused, as is one claiming to be longer than a block can deflate to
+    let bomb = flate::deflate_bytes(Vec::from_elem(DEFLATE_BLOCK + 1, 0u8).as_slice()).unwrap().as_slice().to_vec();
+    let mut packed: Vec<u8> = range(0u, 4).map(|i| (bomb.len() >> (24 - 8 * i)) as u8).collect();
+    packed.push_all(bomb.as_slice());
+
+    let mut claimed: Vec<u8> = vec![0x7f, 0xff, 0xff, 0xff];
+    claimed.push_all(bomb.as_slice());
+
+    for content in [packed, claimed].iter() {
+        match decompress(FLAG_DEFLATE, content.clone()) {
+            Err(IntegrityError) => { },
+            _                   => fail!("inflated past a block"),
+        }
+    }
+
+    let zeros: Vec<u8> = Vec::from_elem(3 * DEFLATE_BLOCK + 5, 0u8);
+    assert!(decompress(FLAG_DEFLATE, CompressDeflate.compress(zeros.as_slice()).unwrap()).unwrap() == zeros);
+}
+
+#[test]
+fn compressed_pieces_round_trip() {
+    //the repeating item only fits the block deflated, the random one is kept as it is
+    let passwords = [b"text".to_vec(), b"noise".to_vec()];
+    let contents  = [Vec::from_fn(100000, |i| b"dear diary "[i % 11]), test_content(38, 2000)];
+
+    for padding in [PadPowerOfTwo, PadRandom(50)].iter() {
+        let mut plause = test_plause(32768, 38);
+        plause.settings.compression = CompressDeflate;
+        plause.settings.padding     = padding.clone();
+
+        for (password, content) in passwords.iter().zip(contents.iter()) {
+            plause.add(password, content).unwrap();
+        }
+
+        plause.gen().unwrap();
+        assert_extracts(&plause, passwords.as_slice(), contents.as_slice());
+    }
+}
+
+#[test]
+fn volumes_round_trip() {
+    let mut plause = test_plause(4096, 24);
+    plause.settings.volumes = 3;
+
+    let content = test_content(24, 9000);
+    plause.add(&b"split".to_vec(), &content).unwrap();
+
+    let mut volumes: Vec<MemReader> = Vec::new();
+
+    for v in range(0u, 3) {
+        let mut volume = MemWriter::new();
+        plause.gen_volume_to(v, &mut volume).unwrap();
+        volumes.push(MemReader::new(volume.unwrap()));
+    }
+
+    //each volume alone holds a third under its own derived password
+    let mut reader = test_plause(4096, 25);
+    reader.import(&mut MemReader::new(volumes[1].get_ref().to_vec())).unwrap();
+    assert!(reader.extract(&volume_password(&b"split".to_vec(), 1, 3)).unwrap().as_slice() == content[3000..6000]);
+
+    let mut joined = [Some(MemWriter::new()), Some(MemWriter::new())];
+    let results    = reader.extract_volumes(volumes.as_mut_slice(), joined.as_mut_slice(), &[b"split".to_vec(), b"missing".to_vec()]).unwrap();
+
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err() && joined[1].is_none());
+    assert!(joined[0].as_ref().unwrap().get_ref() == content.as_slice());
+}
+
+#[test]
+fn fragments_round_trip() {
+    let mut plause = test_plause(16384, 26);
+    plause.settings.fragments = 4;
+
+    let passwords = vec![b"first".to_vec(), b"second".to_vec()];
+    let contents  = vec![test_content(26, 3000), test_content(27, 2000)];
+
+    for (password, content) in passwords.iter().zip(contents.iter()) {
+        plause.add(password, content).unwrap();
+    }
+
+    plause.gen().unwrap();
+
+    for (password, content) in passwords.iter().zip(contents.iter()) {
+        assert!(plause.extract(password).unwrap() == *content);
+    }
+
+    let mut blob      = MemReader::new(plause.content.clone());
+    let locations     = plause.locate_all(&mut blob, passwords.as_slice()).unwrap();
+    let mut extracted = vec![Some(MemWriter::new()), Some(MemWriter::new())];
+
+    for result in plause.extract_all_located(&mut blob, extracted.as_mut_slice(), locations.as_slice()).unwrap().into_iter() {
+        result.unwrap();
+    }
+
+    for (writer, content) in extracted.into_iter().zip(contents.iter()) {
+        assert!(writer.unwrap().unwrap() == *content);
+    }
+
+    //a stream can't go back for the rest of the item, nothing of it is written
+    let mut streamed = MemWriter::new();
+
+    match plause.extract_to(&mut MemReader::new(plause.content.clone()), &mut streamed, &passwords[0]) {
+        Err(FragmentedPiece) => assert!(streamed.get_ref().is_empty()),
+        _                    => fail!("a fragment was streamed as a whole item"),
+    }
+}
+
+#[test]
+fn digests_round_trip_through_key_file() {
+    let passwords = [b"first".to_vec(), b"second".to_vec()];
+    let contents  = [test_content(28, 3000), test_content(29, 100)];
+    let digests: [DigestKind, ..3] = [DigestSha256, DigestSha512, DigestBlake2b];
+
+    for digest in digests.iter() {
+        let mut plause = test_plause(16384, 28);
+        plause.settings.digest = digest.clone();
+
+        for (password, content) in passwords.iter().zip(contents.iter()) {
+            plause.add(password, content).unwrap();
+        }
+
+        plause.gen().unwrap();
+
+        assert!(reopen(&plause).settings.digest == *digest);
+        assert_extracts(&plause, passwords.as_slice(), contents.as_slice());
+    }
+}
+
+#[test]
+fn streaming_crosses_windows() {
+    //a blob exactly one piece long is that piece, moved into a larger blob so its prefix straddles
+    //the first window edge and its body spans more than a window
+    let window  = 65536u;
+    let content = test_content(30, 100000);
+    let mut plause = test_plause(100000 + 105, 30);
+    plause.add(&b"long".to_vec(), &content).unwrap();
+    plause.gen().unwrap();
+
+    let piece = plause.content.clone();
+    let start = window - 16;
+    let mut blob = test_content(31, 4 * window);
+    blob[mut start..start + piece.len()].copy_from(piece.as_slice());
+
+    plause.content = blob.clone();
+    assert!(plause.extract(&b"long".to_vec()).unwrap() == content);
+
+    let mut streamed = MemWriter::new();
+    plause.extract_to(&mut MemReader::new(blob), &mut streamed, &b"long".to_vec()).unwrap();
+    assert!(streamed.get_ref() == content.as_slice());
+}
+
+/// every needle the matcher reports over haystack as (end, needle), in order
+fn matched(needles: &[Vec<u8>], haystack: &[u8]) -> Vec<(uint, uint)> {
+    let matcher = Matcher::new(needles);
+    let mut state = matcher.start();
+    let mut result: Vec<(uint, uint)> = Vec::new();
+
+    for (i, &b) in haystack.iter().enumerate() {
+        state = matcher.step(state, b);
+
+        let mut found = matcher.matches(state).to_vec();
+        found.sort();
+        result.extend(found.into_iter().map(|m| (i + 1, m)));
+    }
+
+    result
+}
+
+/// the same as matched, found by comparing every needle at every end
+fn matched_naively(needles: &[Vec<u8>], haystack: &[u8]) -> Vec<(uint, uint)> {
+    let mut result: Vec<(uint, uint)> = Vec::new();
+
+    for end in range(1, haystack.len() + 1) {
+        for (m, needle) in needles.iter().enumerate() {
+            if needle.len() <= end && haystack[end - needle.len()..end] == needle.as_slice() {
+                result.push((end, m));
+            }
+        }
+    }
+
+    result
+}
+
+#[test]
+fn matcher_follows_fail_links() {
+    let needles: Vec<Vec<u8>> = ["he", "she", "his", "hers", "aab", "ab", "b", "aaab"].iter().map(|x| x.as_bytes().to_vec()).collect();
+    let haystack = b"ushershishe aaaabaab hershe";
+    assert_eq!(matched(needles.as_slice(), haystack), matched_naively(needles.as_slice(), haystack));
+
+    //a two letter alphabet makes needles overlap and share prefixes all the time
+    for seed in range(0, ROUNDS) {
+        let mut rng = seeded_random(seed);
+        let mut needles: Vec<Vec<u8>> = Vec::new();
+
+        for _ in range(0u, 6) {
+            let len = rng.below(5) as uint + 1;
+            needles.push(Vec::from_fn(len, |_| b'a' + rng.below(2) as u8));
+        }
+
+        let haystack: Vec<u8> = Vec::from_fn(200, |_| b'a' + rng.below(2) as u8);
+
+        assert_eq!(matched(needles.as_slice(), haystack.as_slice()), matched_naively(needles.as_slice(), haystack.as_slice()));
+    }
+}
+
+#[test]
+fn extract_all_answers_every_password() {
+    let mut plause = test_plause(16384, 32);
+    let first  = test_content(32, 2000);
+    let second = test_content(33, 500);
+    plause.add(&b"first".to_vec(), &first).unwrap();
+    plause.add(&b"second".to_vec(), &second).unwrap();
+    plause.gen().unwrap();
+
+    let results = plause.extract_all(&[b"second".to_vec(), b"missing".to_vec(), b"first".to_vec()]);
+    assert_eq!(results.len(), 3);
+
+    match (&results[0], &results[1], &results[2]) {
+        (&Ok(ref a), &Err(NotFound), &Ok(ref b)) => assert!(*a == second && *b == first),
+        _                                        => fail!("extract_all mixed up its passwords"),
+    }
+}
+
+#[test]
+fn idents_hide_the_password_length() {
+    //a piece filling the block exactly, whatever the length of the password it is under
+    let content = test_content(34, 1000);
+
+    for password in [b"a".to_vec(), Vec::from_elem(60, b'z')].iter() {
+        let mut plause = test_plause(1000 + 105, 34);
+        plause.add(password, &content).unwrap();
+        plause.gen().unwrap();
+
+        assert!(plause.extract(password).unwrap() == content);
+    }
+}
+
+#[test]
+fn key_file_refuses_unusable_ident_lengths() {
+    for ident in ["1", "15", "1025", "1099511627776"].iter() {
+        let text = format!("saltysaltsalt\n!version=3\n!ident={}\npassword\n", ident);
+
+        match KeyFile::read(&mut BufferedReader::new(MemReader::new(text.into_bytes()))) {
+            Err(MalformedKeyFile(..)) => { },
+            _                         => fail!("ident length {} was accepted", ident),
+        }
+    }
+}
+
+#[test]
+fn padded_pieces_round_trip() {
+    let passwords = [b"first".to_vec(), b"second".to_vec()];
+    let contents  = [test_content(35, 3000), test_content(36, 10)];
+    let paddings  = [PadPowerOfTwo, PadBuckets(vec![64, 1024, 8192]), PadRandom(100)];
+
+    for padding in paddings.iter() {
+        let mut plause = test_plause(32768, 35);
+        plause.settings.padding = padding.clone();
+
+        for (password, content) in passwords.iter().zip(contents.iter()) {
+            plause.add(password, content).unwrap();
+        }
+
+        plause.gen().unwrap();
+        assert_extracts(&plause, passwords.as_slice(), contents.as_slice());
+    }
+
+    assert!(Padding::from_str("random:100") == Some(PadRandom(100)));
+    assert!(Padding::from_str("random:101").is_none());
+    assert!(Padding::from_str("random:18446744073709551615").is_none());
+}
+
+#[test]
+fn diary_keeps_whole_entries() {
+    let mut rng = seeded_random(37);
+
+    for &len in [shortest_entry(), 60, 200, 5000].iter() {
+        let written = diary(&mut rng, len).unwrap();
+
+        assert!(written.len() <= len);
+        assert!(written.as_slice().ends_with(b"\n\n"));
+    }
+
+    match diary(&mut rng, shortest_entry() - 1) {
+        Err(NoSpace(..)) => { },
+        _                => fail!("a diary was cut short"),
+    }
+}